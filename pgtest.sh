@@ -0,0 +1,39 @@
+#!/bin/sh
+
+# Runs tests which require PostgreSQL using a throwaway cluster.
+#
+# The cluster will be created under a temporary directory and removed after tests.
+# Set PGRUNAS to run the server as another user(postgres refuses to run as root).
+
+set -e
+
+export PGDATA=$(mktemp -d)
+export PGHOST=$PGDATA
+export PGPORT=${PGPORT:-54329}
+export PGUSER=postgres
+export PGDATABASE=postgres
+
+runas() {
+  if [ -n "$PGRUNAS" ]; then
+    su "$PGRUNAS" -c "$*"
+  else
+    sh -c "$*"
+  fi
+}
+
+[ -n "$PGRUNAS" ] && chown "$PGRUNAS" "$PGDATA"
+
+cleanup() {
+  runas pg_ctl --pgdata "$PGDATA" --mode immediate stop >/dev/null 2>&1 || true
+  rm -rf "$PGDATA"
+}
+trap cleanup EXIT
+
+runas initdb --pgdata "$PGDATA" --username "$PGUSER" --auth trust >/dev/null
+runas pg_ctl \
+  --pgdata "$PGDATA" \
+  --options "\"-k $PGDATA -p $PGPORT -c listen_addresses=''\"" \
+  --wait \
+  start >/dev/null
+
+cargo test --features postgres $CARGO_OPTIONS -- --ignored kvstore::postgres
@@ -1,5 +1,6 @@
 //! Key value pairs container.
 
+use crate::namer::{BucketNamer, SimpleBucketNamer};
 use crate::{date::Date, device::Device, evt::Event};
 
 /// ID(name) of a container which may contain many key/value pairs.
@@ -14,6 +15,42 @@ impl Bucket {
         self.name.as_str()
     }
 
+    /// Checks if the name can be used as an identifier(e.g, a table name) without quoting.
+    ///
+    /// Safe names contain only `[a-z0-9_]`, start with a letter or `_`,
+    /// and are at most 63 bytes long.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rs_kv2spacetimedb::bucket::Bucket;
+    ///
+    /// assert!(Bucket::from(String::from("devices_2022_11_02")).is_safe_identifier());
+    /// assert!(!Bucket::from(String::from("dates; DROP TABLE dates")).is_safe_identifier());
+    /// ```
+    pub fn is_safe_identifier(&self) -> bool {
+        let b: &[u8] = self.name.as_bytes();
+        let valid_head: bool = b
+            .first()
+            .map(|c| c.is_ascii_lowercase() || b'_'.eq(c))
+            .unwrap_or(false);
+        let valid_body: bool = b
+            .iter()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b'_'.eq(c));
+        valid_head && valid_body && b.len() <= 63
+    }
+
+    /// Gets the name as str if the name is a safe identifier.
+    pub fn as_safe_identifier(&self) -> Result<&str, Event> {
+        match self.is_safe_identifier() {
+            true => Ok(self.as_str()),
+            false => Err(Event::UnsafeIdentifier(format!(
+                "Unsafe bucket name: {}",
+                self.as_str()
+            ))),
+        }
+    }
+
     /// Creates new `Bucket` for data.
     ///
     /// # Example
@@ -31,10 +68,7 @@ impl Bucket {
     /// );
     /// ```
     pub fn new_data_bucket(dev: &Device, date: &Date) -> Self {
-        let device_id: &str = dev.as_str();
-        let date_str: &str = date.as_str();
-        let name: String = format!("data_{}_{}", date_str, device_id);
-        Self { name }
+        SimpleBucketNamer::default().data(dev, date)
     }
 
     /// Creates new `Bucket` for dates master.
@@ -53,9 +87,7 @@ impl Bucket {
     /// );
     /// ```
     pub fn new_dates_master_for_device(dev: &Device) -> Self {
-        let device_id: &str = dev.as_str();
-        let name: String = format!("dates_{}", device_id);
-        Self { name }
+        SimpleBucketNamer::default().dates4device(dev)
     }
 
     /// Creates new `Bucket` for devices master.
@@ -74,9 +106,7 @@ impl Bucket {
     /// );
     /// ```
     pub fn new_devices_master_for_date(date: &Date) -> Self {
-        let date_str: &str = date.as_str();
-        let name: String = format!("devices_{}", date_str);
-        Self { name }
+        SimpleBucketNamer::default().devices4date(date)
     }
 
     /// Creates new `Bucket` for dates master.
@@ -93,8 +123,7 @@ impl Bucket {
     /// );
     /// ```
     pub fn new_dates_master() -> Self {
-        let name = String::from("dates");
-        Self { name }
+        SimpleBucketNamer::default().dates()
     }
 
     /// Creates new `Bucket` for devices master.
@@ -111,8 +140,70 @@ impl Bucket {
     /// );
     /// ```
     pub fn new_devices_master() -> Self {
-        let name = String::from("devices");
-        Self { name }
+        SimpleBucketNamer::default().devices()
+    }
+}
+
+impl Bucket {
+    /// Parses the bucket name using the default naming scheme.
+    ///
+    /// Names which do not start with known prefixes are `Unknown`.
+    ///
+    /// # Errors
+    /// Known prefix with invalid date/device info.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rs_kv2spacetimedb::{date::Date, device::Device, bucket::{Bucket, BucketKind}};
+    ///
+    /// let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+    /// let date = Date::new_unchecked("2022_11_02".into());
+    ///
+    /// let bucket = Bucket::new_data_bucket(&dev, &date);
+    /// assert_eq!(
+    ///     bucket.parse().unwrap(),
+    ///     BucketKind::Data { date, device: dev },
+    /// );
+    /// ```
+    pub fn parse(&self) -> Result<BucketKind, Event> {
+        SimpleBucketNamer::default().parse(self)
+    }
+}
+
+/// Kind of a bucket with date/device info.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketKind {
+    /// Data bucket(data_2022_11_07_cafef00ddeadbeafface864299792458).
+    Data { date: Date, device: Device },
+
+    /// Dates master bucket for device(dates_cafef00ddeadbeafface864299792458).
+    DatesForDevice(Device),
+
+    /// Devices master bucket for date(devices_2022_11_07).
+    DevicesForDate(Date),
+
+    /// Dates master bucket(dates).
+    Dates,
+
+    /// Devices master bucket(devices).
+    Devices,
+
+    /// Other buckets.
+    Unknown,
+}
+
+impl BucketKind {
+    /// Creates new `Bucket`(`None` for unknown buckets).
+    pub fn to_bucket(&self) -> Option<Bucket> {
+        match self {
+            Self::Data { date, device } => Some(Bucket::new_data_bucket(device, date)),
+            Self::DatesForDevice(device) => Some(Bucket::new_dates_master_for_device(device)),
+            Self::DevicesForDate(date) => Some(Bucket::new_devices_master_for_date(date)),
+            Self::Dates => Some(Bucket::new_dates_master()),
+            Self::Devices => Some(Bucket::new_devices_master()),
+            Self::Unknown => None,
+        }
     }
 }
 
@@ -151,4 +242,92 @@ mod test_bucket {
             assert_eq!(s, "dates");
         }
     }
+
+    mod parse {
+
+        use crate::bucket::{Bucket, BucketKind};
+        use crate::{date::Date, device::Device};
+
+        #[test]
+        fn test_round_trip() {
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date = Date::new_unchecked("2022_11_02".into());
+            let kinds: Vec<BucketKind> = vec![
+                BucketKind::Data {
+                    date: date.clone(),
+                    device: dev.clone(),
+                },
+                BucketKind::DatesForDevice(dev),
+                BucketKind::DevicesForDate(date),
+                BucketKind::Dates,
+                BucketKind::Devices,
+            ];
+            for k in kinds {
+                let b: Bucket = k.to_bucket().unwrap();
+                assert_eq!(b.parse().unwrap(), k);
+            }
+        }
+
+        #[test]
+        fn test_unknown() {
+            let b: Bucket = Bucket::from(String::from("date_2022/11/07"));
+            assert_eq!(b.parse().unwrap(), BucketKind::Unknown);
+            assert_eq!(BucketKind::Unknown.to_bucket(), None);
+        }
+
+        #[test]
+        fn test_invalid() {
+            let names = vec![
+                "data_2022_11_07",
+                "data_2022_11_07_",
+                "data_2022_13_07_cafef00ddeadbeafface864299792458",
+                "data_2022/11/07_cafef00ddeadbeafface864299792458",
+                "dates_",
+                "devices_2022_11_32",
+                "devices_2022_+1_01",
+                "devices_",
+            ];
+            for name in names {
+                let b: Bucket = Bucket::from(String::from(name));
+                assert!(b.parse().is_err(), "must be rejected: {}", name);
+            }
+        }
+    }
+    mod is_safe_identifier {
+
+        use crate::bucket::Bucket;
+
+        #[test]
+        fn test_safe() {
+            let names = vec![
+                "data_2022_11_07_cafef00ddeadbeafface864299792458",
+                "dates",
+                "_tmp",
+            ];
+            for name in names {
+                let b: Bucket = Bucket::from(String::from(name));
+                assert!(b.is_safe_identifier(), "must be accepted: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_unsafe() {
+            let long: String = "a".repeat(64);
+            let names = vec![
+                "",
+                "0dates",
+                "Dates",
+                "dates;",
+                "dates--",
+                "public.dates",
+                "dates\u{0}",
+                long.as_str(),
+            ];
+            for name in names {
+                let b: Bucket = Bucket::from(String::from(name));
+                assert!(!b.is_safe_identifier(), "must be rejected: {}", name);
+                assert!(b.as_safe_identifier().is_err());
+            }
+        }
+    }
 }
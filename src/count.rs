@@ -1,7 +1,7 @@
 use crate::datetime::DateTime;
 
 /// A Counter with updated Date/Time.
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Count {
     count: u64,
     updated: DateTime,
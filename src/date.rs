@@ -1,9 +1,46 @@
 //! Date info which can be used as a part of bucket name.
 
-use crate::{day::Day, month::Month, year::Year};
+use std::str::FromStr;
+
+use crate::datetime::{DateTime, DAY_US};
+use crate::{day::Day, evt::Event, month::Month, weekday::Weekday, year::Year};
+
+/// Counts days from 1970_01_01(proleptic Gregorian calendar).
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y: i64 = match m <= 2 {
+        true => y - 1,
+        false => y,
+    };
+    let era: i64 = y.div_euclid(400);
+    let yoe: i64 = y - era * 400;
+    let mp: i64 = (m + 9) % 12; // Mar => 0, ... , Feb => 11
+    let doy: i64 = (153 * mp + 2) / 5 + d - 1;
+    let doe: i64 = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Converts days from 1970_01_01 to (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z: i64 = days + 719468;
+    let era: i64 = z.div_euclid(146097);
+    let doe: i64 = z - era * 146097;
+    let yoe: i64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy: i64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: i64 = (5 * doy + 2) / 153;
+    let d: i64 = doy - (153 * mp + 2) / 5 + 1;
+    let m: i64 = match mp < 10 {
+        true => mp + 3,
+        false => mp - 9,
+    };
+    let y: i64 = yoe + era * 400;
+    match m <= 2 {
+        true => (y + 1, m, d),
+        false => (y, m, d),
+    }
+}
 
 /// Date info container which contains year/month/date.
-#[derive(PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Date {
     date: String, // 2022/11/01 => 2022_11_01
 }
@@ -12,6 +49,7 @@ impl Date {
     /// Creates new `Date` from `String`.
     ///
     /// Provided `String` must be "valid"; can be used as a part of table name.
+    /// Use [`Date::parse`] for untrusted input.
     ///
     /// # Example
     /// ```
@@ -31,6 +69,8 @@ impl Date {
 
     /// Creates new `Date` which can be invalid.
     ///
+    /// Use [`Date::try_new`] to reject dates like 2023_02_31.
+    ///
     /// # Arguments
     /// - y: Year. Always valid.
     /// - m: Month. Always valid.
@@ -42,6 +82,204 @@ impl Date {
         Self::from_raw(yu, mu, du)
     }
 
+    /// Creates new `Date` which must exist in the calendar(proleptic Gregorian).
+    ///
+    /// # Arguments
+    /// - y: Year(1 ..= 9999).
+    /// - m: Month.
+    /// - d: Day. Must not exceed the number of days of the month.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::{date::Date, day::Day, month::Month, year::Year};
+    ///
+    /// let y = Year::try_from(2024).unwrap();
+    /// let d = Date::try_new(y, Month::Feb, Day::try_from(29).unwrap()).unwrap();
+    /// assert_eq!(d.as_str(), "2024_02_29");
+    ///
+    /// let y = Year::try_from(2023).unwrap();
+    /// assert!(Date::try_new(y, Month::Feb, Day::try_from(29).unwrap()).is_err());
+    /// ```
+    pub fn try_new(y: Year, m: Month, d: Day) -> Result<Self, Event> {
+        let yu: u16 = y.as_raw();
+        let du: u8 = d.as_raw();
+        let max: u8 = m.days(&y);
+        match (yu <= 9999, du <= max) {
+            (false, _) => Err(Event::InvalidYear(format!("Year out of range: {}", yu))),
+            (_, false) => Err(Event::InvalidDay(format!(
+                "Invalid day number: {}(month: {}, max: {})",
+                du,
+                m.as_raw(),
+                max
+            ))),
+            (true, true) => Ok(Self::new(y, m, d)),
+        }
+    }
+
+    fn parse_parts(s: &str) -> Result<(Year, Month, Day), Event> {
+        let invalid = || Event::InvalidDate(format!("Invalid date: {}", s));
+        let b: &[u8] = s.as_bytes();
+        let valid_format: bool = 10 == b.len()
+            && b.iter().enumerate().all(|(i, c)| match i {
+                4 | 7 => b'_'.eq(c),
+                _ => c.is_ascii_digit(),
+            });
+        valid_format.then_some(()).ok_or_else(invalid)?;
+        let ys: &str = s.get(0..4).ok_or_else(invalid)?;
+        let ms: &str = s.get(5..7).ok_or_else(invalid)?;
+        let ds: &str = s.get(8..10).ok_or_else(invalid)?;
+        let yu: u16 = str::parse(ys).map_err(|e| Event::InvalidYear(format!("{}: {}", ys, e)))?;
+        let mu: u8 = str::parse(ms).map_err(|e| Event::InvalidMonth(format!("{}: {}", ms, e)))?;
+        let du: u8 = str::parse(ds).map_err(|e| Event::InvalidDay(format!("{}: {}", ds, e)))?;
+        let y: Year = Year::try_from(yu)?;
+        let m: Month = Month::try_from(mu)?;
+        let d: Day = Day::try_from(du)?;
+        Ok((y, m, d))
+    }
+
+    /// Parses "YYYY_MM_DD" which must exist in the calendar.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::date::Date;
+    ///
+    /// let d = Date::parse("2022_11_01").unwrap();
+    /// assert_eq!(d.as_str(), "2022_11_01");
+    /// assert!(Date::parse("2022-11-01").is_err());
+    /// assert!(Date::parse("2023_02_29").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Self, Event> {
+        let (y, m, d) = Self::parse_parts(s)?;
+        Self::try_new(y, m, d)
+    }
+
+    /// Gets the year part.
+    ///
+    /// Returns error if the date was created from an invalid string.
+    pub fn year(&self) -> Result<Year, Event> {
+        Self::parse_parts(self.as_str()).map(|(y, _, _)| y)
+    }
+
+    /// Gets the month part.
+    ///
+    /// Returns error if the date was created from an invalid string.
+    pub fn month(&self) -> Result<Month, Event> {
+        Self::parse_parts(self.as_str()).map(|(_, m, _)| m)
+    }
+
+    /// Gets the day part.
+    ///
+    /// Returns error if the date was created from an invalid string.
+    pub fn day(&self) -> Result<Day, Event> {
+        Self::parse_parts(self.as_str()).map(|(_, _, d)| d)
+    }
+
+    /// Counts days since 1970_01_01(negative for older dates).
+    ///
+    /// Returns error if the date does not exist in the calendar.
+    pub fn to_days(&self) -> Result<i64, Event> {
+        let (y, m, d) = Self::parse_parts(self.as_str())?;
+        Self::try_new(y, m, d)?;
+        Ok(days_from_civil(
+            y.as_raw().into(),
+            m.as_raw().into(),
+            d.as_raw().into(),
+        ))
+    }
+
+    /// Creates new `Date` from days since 1970_01_01.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::date::Date;
+    ///
+    /// let d = Date::from_days(19297).unwrap();
+    /// assert_eq!(d.as_str(), "2022_11_01");
+    /// ```
+    pub fn from_days(days: i64) -> Result<Self, Event> {
+        let (y, m, d) = civil_from_days(days);
+        match (1..=9999).contains(&y) {
+            true => Ok(Self::from_raw(y as u16, m as u8, d as u8)),
+            false => Err(Event::InvalidDate(format!(
+                "Date out of range: {} days",
+                days
+            ))),
+        }
+    }
+
+    /// Creates new `Date` after(or before if negative) specified days.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::date::Date;
+    ///
+    /// let d = Date::parse("2022_12_31").unwrap();
+    /// assert_eq!(d.add_days(1).unwrap().as_str(), "2023_01_01");
+    /// assert_eq!(d.add_days(-306).unwrap().as_str(), "2022_02_28");
+    /// ```
+    pub fn add_days(&self, days: i32) -> Result<Self, Event> {
+        let base: i64 = self.to_days()?;
+        Self::from_days(base + i64::from(days))
+    }
+
+    /// Gets the next date.
+    pub fn succ(&self) -> Result<Self, Event> {
+        self.add_days(1)
+    }
+
+    /// Gets the previous date.
+    pub fn pred(&self) -> Result<Self, Event> {
+        self.add_days(-1)
+    }
+
+    /// Gets the start of this date(00:00:00 UTC).
+    ///
+    /// Returns error for dates before 1970_01_01.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::date::Date;
+    ///
+    /// let dt = Date::parse("2022_11_07").unwrap().start_of_day_utc().unwrap();
+    /// assert_eq!(dt.as_unixtime_us(), 1_667_779_200_000_000);
+    /// ```
+    pub fn start_of_day_utc(&self) -> Result<DateTime, Event> {
+        let days: i64 = self.to_days()?;
+        let ud: u64 = u64::try_from(days)
+            .map_err(|_| Event::InvalidDateTime(format!("Before epoch: {}", self.as_str())))?;
+        Ok(DateTime::from_unixtime_us(ud * DAY_US))
+    }
+
+    /// Gets the day of week.
+    pub fn weekday(&self) -> Result<Weekday, Event> {
+        let days: i64 = self.to_days()?;
+        // 1970_01_01 => Thu
+        let iso: i64 = (days + 3).rem_euclid(7) + 1;
+        Weekday::try_from(iso as u8)
+    }
+
+    /// Gets the ISO 8601 week(week-numbering year, week number(1 ..= 53)).
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::date::Date;
+    ///
+    /// let (y, w) = Date::parse("2021_01_03").unwrap().iso_week().unwrap();
+    /// assert_eq!((y.as_raw(), w), (2020, 53));
+    /// ```
+    pub fn iso_week(&self) -> Result<(Year, u8), Event> {
+        let days: i64 = self.to_days()?;
+        let wd: i64 = self.weekday()?.as_raw().into();
+        // The week belongs to the year which contains its Thursday.
+        let thu: i64 = days - wd + 4;
+        let (ty, _, _) = civil_from_days(thu);
+        let jan1: i64 = days_from_civil(ty, 1, 1);
+        let week: i64 = (thu - jan1) / 7 + 1;
+        let yu: u16 = u16::try_from(ty)
+            .map_err(|e| Event::InvalidYear(format!("Week year out of range: {}", e)))?;
+        Ok((Year::try_from(yu)?, week as u8))
+    }
+
     /// Gets the date as str.
     pub fn as_str(&self) -> &str {
         self.date.as_str()
@@ -53,6 +291,15 @@ impl Date {
     }
 }
 
+/// Parses "YYYY_MM_DD"(same as [`Date::parse`]).
+impl FromStr for Date {
+    type Err = Event;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
 #[cfg(test)]
 mod test_date {
 
@@ -70,4 +317,117 @@ mod test_date {
             assert_eq!("1970_01_01", date.as_str());
         }
     }
+
+    mod parse {
+
+        use crate::date::Date;
+
+        #[test]
+        fn test_invalid() {
+            assert!(Date::parse("").is_err());
+            assert!(Date::parse("2022_11_1").is_err());
+            assert!(Date::parse("2022_+1_01").is_err());
+            assert!(Date::parse("2022_13_01").is_err());
+            assert!(Date::parse("2022_11_01; DROP TABLE dates").is_err());
+        }
+    }
+    mod try_new {
+
+        use crate::date::Date;
+        use crate::{day::Day, month::Month, year::Year};
+
+        #[test]
+        fn test_month_end() {
+            let y: Year = Year::try_from(2023).unwrap();
+            let d31: Day = Day::try_from(31).unwrap();
+            assert!(Date::try_new(y, Month::Jan, d31).is_ok());
+            assert!(Date::try_new(y, Month::Feb, d31).is_err());
+            assert!(Date::try_new(y, Month::Apr, d31).is_err());
+            assert!(Date::try_new(Year::try_from(10000).unwrap(), Month::Jan, d31).is_err());
+        }
+
+        #[test]
+        fn test_leap() {
+            let d29: Day = Day::try_from(29).unwrap();
+            assert!(Date::try_new(Year::try_from(2000).unwrap(), Month::Feb, d29).is_ok());
+            assert!(Date::try_new(Year::try_from(1900).unwrap(), Month::Feb, d29).is_err());
+        }
+    }
+
+    mod from_str {
+
+        use crate::date::Date;
+        use crate::{day::Day, month::Month, year::Year};
+
+        #[test]
+        fn test_parts() {
+            let d: Date = "2024_02_29".parse().unwrap();
+            assert_eq!(d.year().unwrap(), Year::try_from(2024).unwrap());
+            assert_eq!(d.month().unwrap(), Month::Feb);
+            assert_eq!(d.day().unwrap(), Day::try_from(29).unwrap());
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert!("2023_02_31".parse::<Date>().is_err());
+            let d: Date = Date::new_unchecked("today".into());
+            assert!(d.year().is_err());
+        }
+    }
+    mod add_days {
+
+        use crate::date::Date;
+
+        #[test]
+        fn test_boundaries() {
+            let d = |s: &str| Date::parse(s).unwrap();
+            assert_eq!(d("2024_02_28").succ().unwrap(), d("2024_02_29"));
+            assert_eq!(d("2024_02_29").succ().unwrap(), d("2024_03_01"));
+            assert_eq!(d("2023_02_28").succ().unwrap(), d("2023_03_01"));
+            assert_eq!(d("2023_01_01").pred().unwrap(), d("2022_12_31"));
+            assert_eq!(d("1970_01_01").pred().unwrap(), d("1969_12_31"));
+            assert_eq!(d("2000_01_01").add_days(366).unwrap(), d("2001_01_01"));
+            assert!(d("9999_12_31").succ().is_err());
+            assert!(d("0001_01_01").pred().is_err());
+            assert!(Date::new_unchecked("2023_02_31".into()).succ().is_err());
+        }
+
+        #[test]
+        fn test_round_trip() {
+            let epoch: Date = Date::parse("1970_01_01").unwrap();
+            assert_eq!(epoch.to_days().unwrap(), 0);
+            for days in [-719162, -1, 0, 1, 11016, 19297, 2932896] {
+                let d: Date = Date::from_days(days).unwrap();
+                assert_eq!(d.to_days().unwrap(), days);
+            }
+        }
+    }
+
+    mod weekday {
+
+        use crate::date::Date;
+        use crate::weekday::Weekday;
+
+        #[test]
+        fn test_weekday() {
+            let w = |s: &str| Date::parse(s).unwrap().weekday().unwrap();
+            assert_eq!(w("1970_01_01"), Weekday::Thu);
+            assert_eq!(w("2022_11_07"), Weekday::Mon);
+            assert_eq!(w("2000_02_29"), Weekday::Tue);
+            assert_eq!(w("1969_12_28"), Weekday::Sun);
+        }
+
+        #[test]
+        fn test_iso_week() {
+            let w = |s: &str| {
+                let (y, n) = Date::parse(s).unwrap().iso_week().unwrap();
+                (y.as_raw(), n)
+            };
+            assert_eq!(w("2022_11_07"), (2022, 45));
+            assert_eq!(w("2020_12_31"), (2020, 53));
+            assert_eq!(w("2019_12_30"), (2020, 1));
+            assert_eq!(w("2023_01_01"), (2022, 52));
+            assert_eq!(w("2023_01_02"), (2023, 1));
+        }
+    }
 }
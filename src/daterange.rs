@@ -0,0 +1,164 @@
+//! Range of dates which can be used to iterate over data buckets.
+
+use std::iter::FusedIterator;
+
+use crate::{date::Date, evt::Event};
+
+/// Iterates dates from the start date(inclusive) to the end date(exclusive).
+///
+/// Every date in the range is valid(bounds are checked by the constructors).
+///
+/// # Example
+/// ```
+/// use rs_kv2spacetimedb::{date::Date, daterange::DateRange};
+///
+/// let lbi = Date::parse("2022_12_30").unwrap();
+/// let ube = Date::parse("2023_01_02").unwrap();
+/// let dates: Vec<String> = DateRange::exclusive(&lbi, &ube)
+///     .unwrap()
+///     .map(|d: Date| d.as_str().into())
+///     .collect();
+/// assert_eq!(dates, vec!["2022_12_30", "2022_12_31", "2023_01_01"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DateRange {
+    next: i64,
+    end: i64,
+}
+
+impl DateRange {
+    /// Creates new range which does not contain the end date.
+    ///
+    /// # Arguments
+    /// - lbi: The first date(inclusive).
+    /// - ube: The end date(exclusive).
+    pub fn exclusive(lbi: &Date, ube: &Date) -> Result<Self, Event> {
+        let next: i64 = lbi.to_days()?;
+        let end: i64 = ube.to_days()?;
+        Ok(Self { next, end })
+    }
+
+    /// Creates new range which contains the end date.
+    ///
+    /// # Arguments
+    /// - lbi: The first date(inclusive).
+    /// - ubi: The last date(inclusive).
+    pub fn inclusive(lbi: &Date, ubi: &Date) -> Result<Self, Event> {
+        let next: i64 = lbi.to_days()?;
+        let end: i64 = ubi.to_days()? + 1;
+        Ok(Self { next, end })
+    }
+
+    /// Creates new range which contains the last `n` days(the last date inclusive).
+    ///
+    /// Returns `Err` if the first date is out of range(before 0001_01_01).
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::{date::Date, daterange::DateRange};
+    ///
+    /// let today = Date::parse("2023_03_01").unwrap();
+    /// let dates: Vec<Date> = DateRange::last_n_days(&today, 2).unwrap().collect();
+    /// assert_eq!(dates[0].as_str(), "2023_02_28");
+    /// assert_eq!(dates[1].as_str(), "2023_03_01");
+    /// ```
+    pub fn last_n_days(ubi: &Date, n: u32) -> Result<Self, Event> {
+        let end: i64 = ubi.to_days()? + 1;
+        let next: i64 = end - i64::from(n);
+        if next < end {
+            Date::from_days(next)?;
+        }
+        Ok(Self { next, end })
+    }
+
+    /// Checks if the range contains no date.
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.next
+    }
+}
+
+impl Iterator for DateRange {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.is_empty() {
+            true => None,
+            false => {
+                let d: Option<Date> = Date::from_days(self.next).ok();
+                self.next = match d {
+                    Some(_) => self.next + 1,
+                    None => self.end,
+                };
+                d
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n: usize = usize::try_from(self.end - self.next).unwrap_or(0);
+        (0, Some(n))
+    }
+}
+
+impl FusedIterator for DateRange {}
+
+#[cfg(test)]
+mod test_daterange {
+
+    mod date_range {
+        use crate::{date::Date, daterange::DateRange};
+
+        fn d(s: &str) -> Date {
+            Date::parse(s).unwrap()
+        }
+
+        #[test]
+        fn test_inclusive() {
+            let dates: Vec<Date> = DateRange::inclusive(&d("2024_02_27"), &d("2024_03_01"))
+                .unwrap()
+                .collect();
+            assert_eq!(
+                dates,
+                vec![
+                    d("2024_02_27"),
+                    d("2024_02_28"),
+                    d("2024_02_29"),
+                    d("2024_03_01")
+                ]
+            );
+        }
+
+        #[test]
+        fn test_empty() {
+            let r: DateRange = DateRange::exclusive(&d("2022_11_07"), &d("2022_11_07")).unwrap();
+            assert!(r.is_empty());
+            assert_eq!(r.count(), 0);
+            let r: DateRange = DateRange::inclusive(&d("2022_11_08"), &d("2022_11_07")).unwrap();
+            assert_eq!(r.count(), 0);
+        }
+
+        #[test]
+        fn test_last_n_days() {
+            let r: DateRange = DateRange::last_n_days(&d("2023_01_01"), 366).unwrap();
+            let dates: Vec<Date> = r.collect();
+            assert_eq!(dates.len(), 366);
+            assert_eq!(dates[0], d("2022_01_01"));
+            assert_eq!(dates[365], d("2023_01_01"));
+        }
+
+        #[test]
+        fn test_invalid() {
+            let bad: Date = Date::new_unchecked("2023_02_31".into());
+            assert!(DateRange::inclusive(&bad, &d("2023_03_01")).is_err());
+        }
+
+        #[test]
+        fn test_out_of_range() {
+            assert!(DateRange::last_n_days(&d("0001_01_02"), 3).is_err());
+            let r: DateRange = DateRange::last_n_days(&d("0001_01_02"), 2).unwrap();
+            assert_eq!(r.count(), 2);
+            let r: DateRange = DateRange::last_n_days(&d("0001_01_02"), 0).unwrap();
+            assert!(r.is_empty());
+        }
+    }
+}
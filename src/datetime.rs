@@ -1,6 +1,12 @@
 //! Simple Date/Time.
 
-use crate::evt::Event;
+use crate::{date::Date, evt::Event};
+
+/// Micro seconds per day.
+pub(crate) const DAY_US: u64 = 86_400_000_000;
+
+/// Micro seconds per minute.
+const MINUTE_US: i64 = 60_000_000;
 
 /// Non-monotonic Date/Time which counts micro seconds from the unix epoch.
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
@@ -36,6 +42,217 @@ impl DateTime {
     }
 }
 
+impl DateTime {
+    /// Gets the date(UTC) of this Date/Time.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::from_unixtime_us(1_667_779_200_000_000); // 2022-11-07T00:00:00Z
+    /// assert_eq!(dt.to_date_utc().unwrap().as_str(), "2022_11_07");
+    /// assert_eq!(dt.sub(1).unwrap().to_date_utc().unwrap().as_str(), "2022_11_06");
+    /// ```
+    pub fn to_date_utc(&self) -> Result<Date, Event> {
+        let days: u64 = self.unixtime_us / DAY_US;
+        Date::from_days(days as i64)
+    }
+
+    /// Gets the local date of this Date/Time using the fixed offset.
+    ///
+    /// # Arguments
+    /// - offset_minutes: Offset from UTC in minutes(e.g, +540 for +09:00, must be within a day).
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::from_unixtime_us(1_667_779_200_000_000); // 2022-11-07T00:00:00Z
+    /// assert_eq!(dt.to_date_with_offset(540).unwrap().as_str(), "2022_11_07");
+    /// assert_eq!(dt.to_date_with_offset(-1).unwrap().as_str(), "2022_11_06");
+    /// ```
+    pub fn to_date_with_offset(&self, offset_minutes: i32) -> Result<Date, Event> {
+        let local_us: i128 = self.local_us(offset_minutes)?;
+        let days: i128 = local_us.div_euclid(i128::from(DAY_US));
+        Date::from_days(days as i64)
+    }
+
+    fn local_us(&self, offset_minutes: i32) -> Result<i128, Event> {
+        let offset_valid: bool = (-1439..=1439).contains(&offset_minutes);
+        offset_valid.then_some(()).ok_or_else(|| {
+            Event::InvalidDateTime(format!("Invalid offset: {} minutes", offset_minutes))
+        })?;
+        Ok(i128::from(self.unixtime_us) + i128::from(i64::from(offset_minutes) * MINUTE_US))
+    }
+}
+
+/// Formats micro seconds from midnight as "HH:MM:SS.ffffff".
+fn tod2str(tod_us: u64) -> String {
+    let us: u64 = tod_us % 1_000_000;
+    let sec: u64 = tod_us / 1_000_000;
+    format!(
+        "{:02}:{:02}:{:02}.{:06}",
+        sec / 3600,
+        (sec / 60) % 60,
+        sec % 60,
+        us
+    )
+}
+
+/// Parses fixed width digits.
+fn str2num(s: &str, invalid: &dyn Fn() -> Event) -> Result<u32, Event> {
+    let digits: bool = !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+    digits.then_some(()).ok_or_else(invalid)?;
+    str::parse(s).map_err(|_| invalid())
+}
+
+/// Parses the fraction of a second(digits after the 6th digit will be ignored).
+fn fraction2us(s: &str, invalid: &dyn Fn() -> Event) -> Result<u64, Event> {
+    let digits: bool = !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+    digits.then_some(()).ok_or_else(invalid)?;
+    let head: &str = s.get(..6).unwrap_or(s);
+    let raw: u32 = str2num(head, invalid)?;
+    Ok(u64::from(raw) * 10_u64.pow(6 - head.len() as u32))
+}
+
+/// Parses "Z" or "+HH:MM" or "-HH:MM" as offset minutes.
+fn offset2minutes(s: &str, invalid: &dyn Fn() -> Event) -> Result<i64, Event> {
+    match s {
+        "Z" | "z" => Ok(0),
+        _ => {
+            let sign: i64 = match s.get(..1) {
+                Some("+") => Ok(1),
+                Some("-") => Ok(-1),
+                _ => Err(invalid()),
+            }?;
+            let valid_format: bool = 6 == s.len() && Some(":") == s.get(3..4);
+            valid_format.then_some(()).ok_or_else(invalid)?;
+            let h: u32 = str2num(s.get(1..3).ok_or_else(invalid)?, invalid)?;
+            let m: u32 = str2num(s.get(4..6).ok_or_else(invalid)?, invalid)?;
+            (h < 24 && m < 60).then_some(()).ok_or_else(invalid)?;
+            Ok(sign * i64::from(h * 60 + m))
+        }
+    }
+}
+
+impl DateTime {
+    /// Parses RFC 3339 date/time(e.g, "2022-11-04T03:46:58.0Z", "2022-11-04T12:46:58+09:00").
+    ///
+    /// Fractions finer than a micro second will be truncated.
+    /// Leap seconds and date/times before the unix epoch are rejected.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::parse_rfc3339("2022-11-04T03:46:58.0Z").unwrap();
+    /// let jst = DateTime::parse_rfc3339("2022-11-04T12:46:58+09:00").unwrap();
+    /// assert_eq!(dt, jst);
+    /// assert_eq!(dt.as_unixtime_us(), 1_667_533_618_000_000);
+    /// ```
+    pub fn parse_rfc3339(s: &str) -> Result<Self, Event> {
+        let invalid = || Event::InvalidDateTime(format!("Invalid RFC 3339 date/time: {}", s));
+        let b: &[u8] = s.as_bytes();
+        let valid_format: bool = 19 < b.len()
+            && b'-' == b[4]
+            && b'-' == b[7]
+            && matches!(b[10], b'T' | b't' | b' ')
+            && b':' == b[13]
+            && b':' == b[16];
+        valid_format.then_some(()).ok_or_else(invalid)?;
+
+        let ds: String = s.get(..10).ok_or_else(invalid)?.replace('-', "_");
+        let date: Date = Date::parse(ds.as_str()).map_err(|_| invalid())?;
+        let days: i64 = date.to_days()?;
+
+        let h: u32 = str2num(s.get(11..13).ok_or_else(invalid)?, &invalid)?;
+        let m: u32 = str2num(s.get(14..16).ok_or_else(invalid)?, &invalid)?;
+        let sec: u32 = str2num(s.get(17..19).ok_or_else(invalid)?, &invalid)?;
+        (h < 24 && m < 60 && sec < 60)
+            .then_some(())
+            .ok_or_else(invalid)?;
+
+        let rest: &str = s.get(19..).ok_or_else(invalid)?;
+        let (frac_us, offset): (u64, &str) = match rest.strip_prefix('.') {
+            None => (0, rest),
+            Some(f) => {
+                let end: usize = f.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+                let us: u64 = fraction2us(f.get(..end).ok_or_else(invalid)?, &invalid)?;
+                (us, f.get(end..).ok_or_else(invalid)?)
+            }
+        };
+        let offset_minutes: i64 = offset2minutes(offset, &invalid)?;
+
+        let tod_us: i128 = i128::from(h * 3600 + m * 60 + sec) * 1_000_000 + i128::from(frac_us);
+        let utc_us: i128 =
+            i128::from(days) * i128::from(DAY_US) + tod_us - i128::from(offset_minutes * MINUTE_US);
+        let unixtime_us: u64 = u64::try_from(utc_us)
+            .map_err(|_| Event::InvalidDateTime(format!("Date/Time out of range: {}", s)))?;
+        Ok(Self::from_unixtime_us(unixtime_us))
+    }
+
+    /// Formats this Date/Time as RFC 3339(UTC, micro seconds).
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::from_unixtime_us(1_667_533_618_000_001);
+    /// assert_eq!(dt.format_rfc3339().unwrap(), "2022-11-04T03:46:58.000001Z");
+    /// ```
+    pub fn format_rfc3339(&self) -> Result<String, Event> {
+        self.format_rfc3339_with_offset(0)
+    }
+
+    /// Formats this Date/Time as RFC 3339 using the fixed offset.
+    ///
+    /// # Arguments
+    /// - offset_minutes: Offset from UTC in minutes(0 will be formatted as "Z").
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::from_unixtime_us(1_667_533_618_000_000);
+    /// assert_eq!(
+    ///     dt.format_rfc3339_with_offset(-150).unwrap(),
+    ///     "2022-11-04T01:16:58.000000-02:30",
+    /// );
+    /// ```
+    pub fn format_rfc3339_with_offset(&self, offset_minutes: i32) -> Result<String, Event> {
+        let local_us: i128 = self.local_us(offset_minutes)?;
+        let days: i128 = local_us.div_euclid(i128::from(DAY_US));
+        let tod_us: i128 = local_us.rem_euclid(i128::from(DAY_US));
+        let date: Date = Date::from_days(days as i64)?;
+        let ds: String = date.as_str().replace('_', "-");
+        let offset: String = match offset_minutes {
+            0 => String::from("Z"),
+            _ => {
+                let sign: char = match offset_minutes < 0 {
+                    true => '-',
+                    false => '+',
+                };
+                let abs: u32 = offset_minutes.unsigned_abs();
+                format!("{}{:02}:{:02}", sign, abs / 60, abs % 60)
+            }
+        };
+        Ok(format!("{}T{}{}", ds, tod2str(tod_us as u64), offset))
+    }
+
+    /// Creates a key from the time of day(UTC) like "03:46:58.000000".
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::datetime::DateTime;
+    ///
+    /// let dt = DateTime::parse_rfc3339("2022-11-04T03:46:58.0Z").unwrap();
+    /// assert_eq!(dt.time_of_day_key(), "03:46:58.000000");
+    /// ```
+    pub fn time_of_day_key(&self) -> String {
+        tod2str(self.unixtime_us % DAY_US)
+    }
+}
+
 impl DateTime {
     fn from_std_time(t: std::time::SystemTime) -> Result<Self, Event> {
         let d: std::time::Duration = t
@@ -144,4 +361,115 @@ mod test_datetime {
             assert_eq!(r.is_ok(), true);
         }
     }
+    mod to_date {
+        use crate::date::Date;
+        use crate::datetime::DateTime;
+
+        #[test]
+        fn test_round_trip() {
+            for s in ["1970_01_02", "2000_02_29", "2022_12_31", "2038_01_19"] {
+                let d: Date = Date::parse(s).unwrap();
+                let dt: DateTime = d.start_of_day_utc().unwrap();
+                assert_eq!(dt.to_date_utc().unwrap(), d);
+                assert_eq!(
+                    dt.sub(1).ok().map(|t| t.to_date_utc().unwrap()),
+                    d.pred().ok()
+                );
+            }
+        }
+
+        #[test]
+        fn test_offset() {
+            // 1970-01-01T00:30:00Z
+            let dt: DateTime = DateTime::from_unixtime_us(1_800_000_000);
+            assert_eq!(dt.to_date_with_offset(-30).unwrap().as_str(), "1970_01_01");
+            assert_eq!(dt.to_date_with_offset(-31).unwrap().as_str(), "1969_12_31");
+            assert_eq!(dt.to_date_with_offset(1410).unwrap().as_str(), "1970_01_02");
+            assert!(dt.to_date_with_offset(1440).is_err());
+        }
+
+        #[test]
+        fn test_before_epoch() {
+            let d: Date = Date::parse("1969_12_31").unwrap();
+            assert!(d.start_of_day_utc().is_err());
+        }
+
+        #[test]
+        fn test_out_of_range() {
+            let dt: DateTime = DateTime::from_unixtime_us(u64::MAX);
+            assert!(dt.to_date_utc().is_err());
+        }
+    }
+    mod rfc3339 {
+        use crate::datetime::DateTime;
+
+        #[test]
+        fn test_round_trip() {
+            let names = vec![
+                "1970-01-01T00:00:00.000000Z",
+                "2000-02-29T23:59:59.999999Z",
+                "2022-11-04T03:46:58.123456Z",
+                "9999-12-31T23:59:59.999999Z",
+            ];
+            for name in names {
+                let dt: DateTime = DateTime::parse_rfc3339(name).unwrap();
+                assert_eq!(dt.format_rfc3339().unwrap(), name);
+            }
+        }
+
+        #[test]
+        fn test_offset() {
+            let utc: DateTime = DateTime::parse_rfc3339("2022-11-04T03:46:58Z").unwrap();
+            let neg: DateTime = DateTime::parse_rfc3339("2022-11-03t22:16:58-05:30").unwrap();
+            assert_eq!(utc, neg);
+            assert_eq!(
+                utc.format_rfc3339_with_offset(540).unwrap(),
+                "2022-11-04T12:46:58.000000+09:00"
+            );
+            assert_eq!(
+                utc.format_rfc3339_with_offset(-330).unwrap(),
+                "2022-11-03T22:16:58.000000-05:30"
+            );
+        }
+
+        #[test]
+        fn test_fraction() {
+            let p = |s: &str| DateTime::parse_rfc3339(s).unwrap().as_unixtime_us();
+            assert_eq!(p("1970-01-01T00:00:00.5Z"), 500_000);
+            assert_eq!(p("1970-01-01T00:00:00.000001Z"), 1);
+            assert_eq!(p("1970-01-01T00:00:00.0000019Z"), 1);
+        }
+
+        #[test]
+        fn test_invalid() {
+            let names = vec![
+                "",
+                "2022-11-04",
+                "2022-11-04T03:46:58",
+                "2022-11-04T03:46:58.Z",
+                "2022-11-04T24:00:00Z",
+                "2022-11-04T03:60:00Z",
+                "2022-11-04T03:46:60Z",
+                "2023-02-29T03:46:58Z",
+                "2022_11_04T03:46:58Z",
+                "2022-11-04T03:46:58+0900",
+                "2022-11-04T03:46:58+24:00",
+                "2022-11-04T03:46:58Zjunk",
+                "1969-12-31T23:59:59Z",
+                "1970-01-01T00:00:00+00:01",
+            ];
+            for name in names {
+                let r: Result<_, _> = DateTime::parse_rfc3339(name);
+                assert!(r.is_err(), "must be rejected: {}", name);
+            }
+        }
+
+        #[test]
+        fn test_time_of_day_key() {
+            let dt: DateTime = DateTime::parse_rfc3339("2022-11-04T03:46:58.0Z").unwrap();
+            assert_eq!(dt.time_of_day_key(), "03:46:58.000000");
+            let dt: DateTime = DateTime::parse_rfc3339("2022-11-04T00:30:21.25+09:00").unwrap();
+            assert_eq!(dt.time_of_day_key(), "15:30:21.250000");
+        }
+    }
 }
@@ -3,6 +3,7 @@
 use crate::evt::Event;
 
 /// Simple "Day" like number(1..=31) which does not care a month nor a leap year.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Day {
     d: u8,
 }
@@ -1,6 +1,9 @@
 //! Device ID which can be used as a part of bucket name.
 
+use crate::evt::Event;
+
 /// Device info container.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Device {
     id: String, // cafef00d-dead-beaf-face-864299792458 => cafef00ddeadbeafface864299792458
 }
@@ -16,6 +19,7 @@ impl Device {
     /// Creates new `Device` from `String`.
     ///
     /// Provided `String` must be "valid"; can be used as a part of table name.
+    /// Use [`Device::try_from_hex`] or [`Device::from_uuid_str`] for untrusted input.
     ///
     /// # Example
     /// ```
@@ -28,6 +32,45 @@ impl Device {
         Self { id }
     }
 
+    /// Creates new `Device` from hex string(1 to 32 hex digits).
+    ///
+    /// Upper case digits will be converted to lower case.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::device::Device;
+    ///
+    /// let d = Device::try_from_hex("CAFEF00DDEADBEAFFACE864299792458").unwrap();
+    /// assert_eq!(d.as_str(), "cafef00ddeadbeafface864299792458");
+    /// assert!(Device::try_from_hex("cafef00d; DROP TABLE dates").is_err());
+    /// ```
+    pub fn try_from_hex(hex: &str) -> Result<Self, Event> {
+        let valid: bool =
+            (1..=32).contains(&hex.len()) && hex.bytes().all(|c| c.is_ascii_hexdigit());
+        match valid {
+            true => Ok(Self::new_unchecked(hex.to_ascii_lowercase())),
+            false => Err(Event::InvalidDevice(format!("Invalid hex id: {}", hex))),
+        }
+    }
+
+    /// Creates new `Device` from uuid string(dashes will be removed).
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::device::Device;
+    ///
+    /// let d = Device::from_uuid_str("cafef00d-dead-beaf-face-864299792458").unwrap();
+    /// assert_eq!(d.as_str(), "cafef00ddeadbeafface864299792458");
+    /// ```
+    pub fn from_uuid_str(uuid: &str) -> Result<Self, Event> {
+        let invalid = || Event::InvalidDevice(format!("Invalid uuid: {}", uuid));
+        let lengths: Vec<usize> = uuid.split('-').map(|s: &str| s.len()).collect();
+        let valid: bool = lengths.eq(&[8, 4, 4, 4, 12]);
+        valid.then_some(()).ok_or_else(invalid)?;
+        let hex: String = uuid.split('-').collect();
+        Self::try_from_hex(hex.as_str()).map_err(|_| invalid())
+    }
+
     /// Gets the device id as str.
     pub fn as_str(&self) -> &str {
         self.id.as_str()
@@ -51,4 +94,33 @@ mod test_device {
             assert_eq!(d.as_str(), "00000000000000000000000000000000");
         }
     }
+
+    mod try_from_hex {
+        use crate::device::Device;
+
+        #[test]
+        fn test_invalid() {
+            assert!(Device::try_from_hex("").is_err());
+            assert!(Device::try_from_hex("cafef00g").is_err());
+            assert!(Device::try_from_hex("cafef00d_").is_err());
+            assert!(Device::try_from_hex("cafef00ddeadbeafface8642997924580").is_err());
+        }
+    }
+
+    mod from_uuid_str {
+        use crate::device::Device;
+
+        #[test]
+        fn test_upper() {
+            let d: Device = Device::from_uuid_str("CAFEF00D-DEAD-BEAF-FACE-864299792458").unwrap();
+            assert_eq!(d.as_str(), "cafef00ddeadbeafface864299792458");
+        }
+
+        #[test]
+        fn test_invalid() {
+            assert!(Device::from_uuid_str("cafef00ddeadbeafface864299792458").is_err());
+            assert!(Device::from_uuid_str("cafef00d-dead-beaf-face8-64299792458").is_err());
+            assert!(Device::from_uuid_str("cafef00d-dead-beaf-face-86429979245x").is_err());
+        }
+    }
 }
@@ -1,9 +1,15 @@
 //! List of events(errors).
 
+use std::error::Error;
+use std::fmt;
+
 use crate::count::Count;
 
 /// List of request handle results.
+///
+/// New variants may be added in minor versions.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Event {
     /// Connection Error to external db.
     ConnectError(String),
@@ -23,11 +29,169 @@ pub enum Event {
     /// Invalid Date/Time
     InvalidDateTime(String),
 
+    /// Invalid device id.
+    InvalidDevice(String),
+
+    /// Invalid date string.
+    InvalidDate(String),
+
+    /// The bucket name can not be used as an identifier(e.g, a table name).
+    UnsafeIdentifier(String),
+
     /// Count cache writer error
     UnableToUpdateCache(Count),
 
     /// Stale count cache
     CountCacheStale(Count),
 
+    /// The bucket does not exist.
+    BucketNotFound(String),
+
+    /// The count is not cached yet(cache miss).
+    NotCached(String),
+
+    /// Conflicting concurrent change(e.g, constraint violation).
+    Conflict(String),
+
+    /// Timeout / canceled request.
+    Timeout(String),
+
+    /// Vendor specific backend error.
+    BackendError {
+        message: String,
+        /// The request may succeed if retried.
+        retriable: bool,
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
+
     UnexpectedError(String),
 }
+
+impl Event {
+    /// Creates new `BackendError` from the backend specific error.
+    pub fn backend<E>(message: String, retriable: bool, source: E) -> Self
+    where
+        E: Error + Send + Sync + 'static,
+    {
+        Self::BackendError {
+            message,
+            retriable,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    /// Gets the stable error code which can be used for logging/metrics.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::evt::Event;
+    ///
+    /// let e = Event::BucketNotFound("dates".into());
+    /// assert_eq!(e.code(), "bucket_not_found");
+    /// assert_eq!(e.to_string(), "bucket_not_found: dates");
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ConnectError(_) => "connect_error",
+            Self::InvalidBucket(_) => "invalid_bucket",
+            Self::InvalidYear(_) => "invalid_year",
+            Self::InvalidMonth(_) => "invalid_month",
+            Self::InvalidDay(_) => "invalid_day",
+            Self::InvalidDateTime(_) => "invalid_date_time",
+            Self::InvalidDevice(_) => "invalid_device",
+            Self::InvalidDate(_) => "invalid_date",
+            Self::UnsafeIdentifier(_) => "unsafe_identifier",
+            Self::UnableToUpdateCache(_) => "unable_to_update_cache",
+            Self::CountCacheStale(_) => "count_cache_stale",
+            Self::BucketNotFound(_) => "bucket_not_found",
+            Self::NotCached(_) => "not_cached",
+            Self::Conflict(_) => "conflict",
+            Self::Timeout(_) => "timeout",
+            Self::BackendError { .. } => "backend_error",
+            Self::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+
+    /// Checks if the request may succeed if retried.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::Timeout(_) => true,
+            Self::BackendError { retriable, .. } => *retriable,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code: &str = self.code();
+        match self {
+            Self::ConnectError(s)
+            | Self::InvalidBucket(s)
+            | Self::InvalidYear(s)
+            | Self::InvalidMonth(s)
+            | Self::InvalidDay(s)
+            | Self::InvalidDateTime(s)
+            | Self::InvalidDevice(s)
+            | Self::InvalidDate(s)
+            | Self::UnsafeIdentifier(s)
+            | Self::BucketNotFound(s)
+            | Self::NotCached(s)
+            | Self::Conflict(s)
+            | Self::Timeout(s)
+            | Self::UnexpectedError(s) => write!(f, "{}: {}", code, s),
+            Self::UnableToUpdateCache(c) | Self::CountCacheStale(c) => write!(
+                f,
+                "{}: count={} updated(us)={}",
+                code,
+                c.as_count(),
+                c.as_datetime().as_unixtime_us()
+            ),
+            Self::BackendError {
+                message, retriable, ..
+            } => write!(f, "{}: {}(retriable: {})", code, message, retriable),
+        }
+    }
+}
+
+impl Error for Event {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::BackendError {
+                source: Some(e), ..
+            } => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_evt {
+
+    mod event {
+        use std::error::Error;
+
+        use crate::evt::Event;
+
+        #[test]
+        fn test_boxed() {
+            let f = || -> Result<(), Box<dyn Error>> {
+                Err(Event::Timeout("upsert".into()))?;
+                Ok(())
+            };
+            let e: Box<dyn Error> = f().unwrap_err();
+            assert_eq!(e.to_string(), "timeout: upsert");
+        }
+
+        #[test]
+        fn test_source() {
+            let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+            let e: Event = Event::backend("Unable to upsert".into(), true, io);
+            assert_eq!(e.code(), "backend_error");
+            assert!(e.is_retriable());
+            assert_eq!(e.source().map(|s| s.to_string()), Some("reset".into()));
+            assert!(!Event::Conflict("dup".into()).is_retriable());
+            assert!(Event::Conflict("dup".into()).source().is_none());
+        }
+    }
+}
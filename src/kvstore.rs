@@ -1,9 +1,24 @@
 //! Key/Value store modules.
 
+pub mod archive;
 pub mod bucket;
+pub mod conflict;
 pub mod count;
 pub mod create;
 pub mod delete;
+pub mod facade;
+pub mod fsck;
 pub mod get;
 pub mod list;
+pub mod mem;
+#[cfg(feature = "async")]
+pub mod nonblocking;
+pub mod parallel;
+pub mod plan;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod rebuild;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod transaction;
 pub mod upsert;
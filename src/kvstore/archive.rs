@@ -0,0 +1,487 @@
+//! Archives buckets before drop.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::item::{Item, RawItem};
+use crate::remove::{is_delete_target, is_drop_target_stale};
+use crate::{bucket::Bucket, date::Date, evt::Event};
+
+use crate::kvstore::delete::{remove_report, DeleteRange, DeleteRow, DropBucket, RemovalReport};
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::list::{ListBuckets, ListKeysAfter};
+
+/// Saves all items of a bucket(e.g, before drop).
+pub trait Archiver {
+    /// Archives all items of the bucket and returns number of archived items.
+    ///
+    /// The archive must not be considered complete if an error is returned.
+    fn archive<I>(&mut self, b: &Bucket, items: I) -> Result<u64, Event>
+    where
+        I: Iterator<Item = Result<RawItem, Event>>;
+}
+
+fn io2event(message: &str) -> impl Fn(std::io::Error) -> Event + '_ {
+    move |e: std::io::Error| Event::backend(format!("{}: {}", message, e), false, e)
+}
+
+fn bytes2hex(b: &[u8]) -> String {
+    b.iter().map(|u| format!("{:02x}", u)).collect()
+}
+
+/// Writes an archive file per bucket into a directory.
+///
+/// Items are written into a temporary file which will be renamed after all items are written.
+/// The temporary file will be removed on error.
+fn archive2dir<I, W>(dir: &Path, b: &Bucket, ext: &str, items: I, write: W) -> Result<u64, Event>
+where
+    I: Iterator<Item = Result<RawItem, Event>>,
+    W: FnMut(&mut BufWriter<File>, &RawItem) -> std::io::Result<()>,
+{
+    let name: &str = b.as_str();
+    let valid_name: bool = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"_.-".contains(&c));
+    valid_name
+        .then_some(())
+        .ok_or_else(|| Event::UnsafeIdentifier(format!("Unsafe file name: {}", name)))?;
+    let filename: PathBuf = dir.join(format!("{}.{}", name, ext));
+    let tmpname: PathBuf = dir.join(format!("{}.{}.tmp", name, ext));
+    let f: File = File::create(&tmpname).map_err(io2event("Unable to create an archive"))?;
+    let written: Result<u64, Event> = write_items(f, items, write);
+    match written {
+        Ok(cnt) => fs::rename(&tmpname, filename)
+            .map(|_| cnt)
+            .map_err(io2event("Unable to rename an archive"))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmpname);
+            }),
+        Err(e) => {
+            let _ = fs::remove_file(&tmpname);
+            Err(e)
+        }
+    }
+}
+
+/// Writes all items into the file and syncs it.
+fn write_items<I, W>(f: File, items: I, mut write: W) -> Result<u64, Event>
+where
+    I: Iterator<Item = Result<RawItem, Event>>,
+    W: FnMut(&mut BufWriter<File>, &RawItem) -> std::io::Result<()>,
+{
+    let mut bw = BufWriter::new(f);
+    let cnt: u64 = items.into_iter().try_fold(0, |tot, r| {
+        let i: RawItem = r?;
+        write(&mut bw, &i).map_err(io2event("Unable to write an item"))?;
+        Ok::<_, Event>(tot + 1)
+    })?;
+    let f: File = bw
+        .into_inner()
+        .map_err(|e| io2event("Unable to flush an archive")(e.into_error()))?;
+    f.sync_all()
+        .map_err(io2event("Unable to sync an archive"))?;
+    Ok(cnt)
+}
+
+/// Writes `{bucket}.ndjson` files which contain lines like `{"key":"<hex>","val":"<hex>"}`.
+#[derive(Debug, Clone)]
+pub struct NdjsonDirArchiver {
+    dir: PathBuf,
+}
+
+impl NdjsonDirArchiver {
+    /// Creates new archiver which writes files into the existing directory.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let dir: PathBuf = dir.as_ref().to_path_buf();
+        Self { dir }
+    }
+}
+
+impl Archiver for NdjsonDirArchiver {
+    fn archive<I>(&mut self, b: &Bucket, items: I) -> Result<u64, Event>
+    where
+        I: Iterator<Item = Result<RawItem, Event>>,
+    {
+        archive2dir(&self.dir, b, "ndjson", items, |w, i: &RawItem| {
+            writeln!(
+                w,
+                r#"{{"key":"{}","val":"{}"}}"#,
+                bytes2hex(i.as_key()),
+                bytes2hex(i.as_val())
+            )
+        })
+    }
+}
+
+/// Writes `{bucket}.bin` files which contain length-prefixed items.
+///
+/// Each item: key length(u32, big endian), key, value length(u32, big endian), value.
+#[derive(Debug, Clone)]
+pub struct BinaryDirArchiver {
+    dir: PathBuf,
+}
+
+impl BinaryDirArchiver {
+    /// Creates new archiver which writes files into the existing directory.
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        let dir: PathBuf = dir.as_ref().to_path_buf();
+        Self { dir }
+    }
+}
+
+fn write_prefixed<W>(w: &mut W, b: &[u8]) -> std::io::Result<()>
+where
+    W: Write,
+{
+    let len: u32 = u32::try_from(b.len())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(b)
+}
+
+impl Archiver for BinaryDirArchiver {
+    fn archive<I>(&mut self, b: &Bucket, items: I) -> Result<u64, Event>
+    where
+        I: Iterator<Item = Result<RawItem, Event>>,
+    {
+        archive2dir(&self.dir, b, "bin", items, |w, i: &RawItem| {
+            write_prefixed(w, i.as_key())?;
+            write_prefixed(w, i.as_val())
+        })
+    }
+}
+
+/// Number of keys to be read at once while archiving.
+const ARCHIVE_PAGE_SIZE: u32 = 1024;
+
+/// Reads items of a bucket page by page(keys first, then values).
+struct BucketItems<'a, S> {
+    store: &'a mut S,
+    b: &'a Bucket,
+    page: std::vec::IntoIter<Vec<u8>>,
+    last: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<S> BucketItems<'_, S>
+where
+    S: ListKeysAfter + GetRaw,
+{
+    fn next_key(&mut self) -> Result<Option<Vec<u8>>, Event> {
+        match self.page.next() {
+            Some(key) => Ok(Some(key)),
+            None => {
+                let keys: Vec<Vec<u8>> =
+                    self.store
+                        .list_after(self.b, self.last.as_deref(), ARCHIVE_PAGE_SIZE)?;
+                self.page = keys.into_iter();
+                Ok(self.page.next())
+            }
+        }
+    }
+
+    fn next_item(&mut self) -> Result<Option<RawItem>, Event> {
+        let key: Vec<u8> = match self.next_key()? {
+            None => return Ok(None),
+            Some(key) => key,
+        };
+        let val: Vec<u8> = self.store.get(self.b, &key)?.ok_or_else(|| {
+            Event::UnexpectedError(format!(
+                "Value removed while archiving: bucket={}, key={}",
+                self.b.as_str(),
+                bytes2hex(&key)
+            ))
+        })?;
+        self.last = Some(key.clone());
+        Ok(Some(Item::new(key, val)))
+    }
+}
+
+impl<S> Iterator for BucketItems<'_, S>
+where
+    S: ListKeysAfter + GetRaw,
+{
+    type Item = Result<RawItem, Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.done {
+            true => None,
+            false => {
+                let r: Result<Option<RawItem>, Event> = self.next_item();
+                self.done = !matches!(r, Ok(Some(_)));
+                r.transpose()
+            }
+        }
+    }
+}
+
+/// Archives all items of the bucket.
+///
+/// Keys are read page by page and values are read one by one.
+/// Returns an error if a listed key has no value(e.g, removed while archiving).
+///
+/// # Arguments
+/// - store: Gets keys page by page and values from the bucket.
+/// - archiver: Saves the items.
+/// - b: Target bucket.
+pub fn archive_bucket<S, A>(store: &mut S, archiver: &mut A, b: &Bucket) -> Result<u64, Event>
+where
+    S: ListKeysAfter + GetRaw,
+    A: Archiver,
+{
+    let items = BucketItems {
+        store,
+        b,
+        page: Vec::new().into_iter(),
+        last: None,
+        done: false,
+    };
+    archiver.archive(b, items)
+}
+
+/// Drops stale buckets after archiving, deletes stale rows and reports each action.
+///
+/// Every date key older than lbi will be deleted(same as `delete_stale_data_range`).
+///
+/// Stops at the first error: a bucket will not be dropped if archiving fails.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets/keys/values, Drops a bucket, Deletes rows.
+/// - archiver: Saves items of buckets to be dropped.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_archived<D, A, T, R>(
+    drop_del_list: D,
+    archiver: &mut A,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: Date,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets + ListKeysAfter + GetRaw,
+    A: Archiver,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    remove_report(
+        drop_del_list,
+        &|b: &Bucket| drop_target(b, &lbi),
+        remove_target,
+        |d: &mut D, b: &Bucket| archive_bucket(d, archiver, b).map(|_| ()),
+        |d: &mut D, b: &Bucket| d.delete_range(b, &[], lbi.as_bytes()),
+    )
+}
+
+/// Archives and drops stale buckets, deletes stale rows using default checkers.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets/keys/values, Drops a bucket, Deletes rows.
+/// - archiver: Saves items of buckets to be dropped.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_archived_default<D, A>(
+    drop_del_list: D,
+    archiver: &mut A,
+    lbi: Date,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets + ListKeysAfter + GetRaw,
+    A: Archiver,
+{
+    delete_stale_data_archived(
+        drop_del_list,
+        archiver,
+        &is_drop_target_stale,
+        &is_delete_target,
+        lbi,
+    )
+}
+
+#[cfg(test)]
+mod test_archive {
+
+    mod delete_stale_data_archived_default {
+        use std::path::PathBuf;
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::archive::{self, Archiver, BinaryDirArchiver, NdjsonDirArchiver};
+        use crate::kvstore::delete::RemovalReport;
+        use crate::kvstore::fsck;
+        use crate::kvstore::get::GetRaw;
+        use crate::kvstore::list::{ListBuckets, ListKeysAfter};
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device, evt::Event};
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_01".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source.into_iter(), &mut cu).unwrap();
+            m
+        }
+
+        fn tmpdir(name: &str) -> PathBuf {
+            let d: PathBuf = std::env::temp_dir().join(format!(
+                "rs-kv2spacetimedb-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&d).unwrap();
+            d
+        }
+
+        struct Failing;
+
+        impl Archiver for Failing {
+            fn archive<I>(&mut self, b: &Bucket, _items: I) -> Result<u64, Event>
+            where
+                I: Iterator<Item = Result<RawItem, Event>>,
+            {
+                Err(Event::UnexpectedError(b.as_str().into()))
+            }
+        }
+
+        /// Store which lost all values after listing keys.
+        struct Vanishing(MemStore);
+
+        impl ListKeysAfter for Vanishing {
+            fn list_after(
+                &mut self,
+                b: &Bucket,
+                after: Option<&[u8]>,
+                limit: u32,
+            ) -> Result<Vec<Vec<u8>>, Event> {
+                self.0.list_after(b, after, limit)
+            }
+        }
+
+        impl GetRaw for Vanishing {
+            fn get(&mut self, _b: &Bucket, _key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+                Ok(None)
+            }
+
+            fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+                self.0.chk(b)
+            }
+        }
+
+        fn data_bucket() -> Bucket {
+            Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ))
+        }
+
+        #[test]
+        fn test_ndjson() {
+            let mut m: MemStore = store();
+            let dir: PathBuf = tmpdir("ndjson");
+            let mut a = NdjsonDirArchiver::new(&dir);
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            let r: RemovalReport = archive::delete_stale_data_archived_default(&mut m, &mut a, lbi);
+            assert!(r.is_ok());
+            assert_eq!(r.dropped().len(), 2);
+
+            let data: String = std::fs::read_to_string(
+                dir.join("data_2022_11_01_cafef00ddeadbeafface864299792458.ndjson"),
+            )
+            .unwrap();
+            assert_eq!(
+                data,
+                "{\"key\":\"30303a33303a32312e305a\",\"val\":\"3432\"}\n"
+            );
+            let dev: String =
+                std::fs::read_to_string(dir.join("devices_2022_11_01.ndjson")).unwrap();
+            assert_eq!(dev.lines().count(), 1);
+            std::fs::remove_dir_all(dir).unwrap();
+
+            let dates: Bucket = Bucket::new_dates_master();
+            assert!(GetRaw::get(&mut m, &dates, b"2022_11_01")
+                .unwrap()
+                .is_none());
+            assert!(GetRaw::get(&mut m, &dates, b"2022_11_02")
+                .unwrap()
+                .is_some());
+            assert!(fsck::fsck(&mut m).unwrap().is_consistent());
+        }
+
+        #[test]
+        fn test_binary() {
+            let mut m: MemStore = store();
+            let dir: PathBuf = tmpdir("binary");
+            let mut a = BinaryDirArchiver::new(&dir);
+            let b: Bucket = data_bucket();
+            let cnt: u64 = archive::archive_bucket(&mut m, &mut a, &b).unwrap();
+            assert_eq!(cnt, 1);
+            let data: Vec<u8> = std::fs::read(dir.join(format!("{}.bin", b.as_str()))).unwrap();
+            let mut expected: Vec<u8> = vec![0, 0, 0, 11];
+            expected.extend_from_slice(b"00:30:21.0Z");
+            expected.extend_from_slice(&[0, 0, 0, 3]);
+            expected.extend_from_slice(b"634");
+            assert_eq!(data, expected);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn test_missing_value() {
+            let mut v = Vanishing(store());
+            let dir: PathBuf = tmpdir("missing");
+            let mut a = NdjsonDirArchiver::new(&dir);
+            let r: Result<u64, Event> = archive::archive_bucket(&mut v, &mut a, &data_bucket());
+            assert!(matches!(r, Err(Event::UnexpectedError(_))));
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn test_tmp_removed() {
+            let dir: PathBuf = tmpdir("tmp");
+            let mut a = BinaryDirArchiver::new(&dir);
+            let items = vec![
+                Ok(Item::new(b"k".to_vec(), b"v".to_vec())),
+                Err(Event::Timeout("get".into())),
+            ];
+            let r: Result<u64, Event> = a.archive(&data_bucket(), items.into_iter());
+            assert!(matches!(r, Err(Event::Timeout(_))));
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+
+        #[test]
+        fn test_skip_drop_on_failure() {
+            let mut m: MemStore = store();
+            let before: Vec<Bucket> = ListBuckets::list(&mut m).unwrap();
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            let r: RemovalReport =
+                archive::delete_stale_data_archived_default(&mut m, &mut Failing, lbi);
+            assert!(!r.is_ok());
+            assert!(r.dropped().is_empty());
+            assert!(r.attempted_drops().is_empty());
+            assert_eq!(
+                r.failed().map(|b| b.as_str()),
+                Some("data_2022_11_01_cafef00ddeadbeafface864299792458")
+            );
+            assert_eq!(ListBuckets::list(&mut m).unwrap(), before);
+        }
+    }
+}
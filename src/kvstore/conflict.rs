@@ -0,0 +1,212 @@
+//! Resolves conflicts between existing values and upserted values.
+
+use std::fmt;
+
+use crate::item::RawItem;
+use crate::{bucket::Bucket, datetime::DateTime, evt::Event};
+
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::upsert::UpsertRaw;
+
+/// Gets the embedded time from a value(`None` if not available).
+pub type DateTimeExtractor = Box<dyn Fn(&[u8]) -> Option<DateTime> + Send + Sync>;
+
+/// Merges an existing value(first argument) and an upserted value(second argument).
+pub type Merger = Box<dyn Fn(&[u8], &[u8]) -> Vec<u8> + Send + Sync>;
+
+/// Decides which value will be saved when the key already exists.
+#[derive(Default)]
+pub enum ConflictPolicy {
+    /// Saves the upserted value(last write wins).
+    #[default]
+    Overwrite,
+
+    /// Keeps the existing value(first write wins).
+    KeepExisting,
+
+    /// Saves the upserted value only if its embedded time is newer.
+    ///
+    /// - The upserted value will be saved if the existing time is not available.
+    /// - The existing value will be kept if the upserted time is not available.
+    NewerByDateTime(DateTimeExtractor),
+
+    /// Saves the merged value.
+    Merge(Merger),
+}
+
+impl ConflictPolicy {
+    /// Gets the value to be saved(`None` if nothing to be saved).
+    ///
+    /// # Arguments
+    /// - existing: The existing value(`None` if the key does not exist).
+    /// - upserted: The upserted value.
+    ///
+    /// # Example
+    /// ```
+    /// use rs_kv2spacetimedb::kvstore::conflict::ConflictPolicy;
+    ///
+    /// let p = ConflictPolicy::KeepExisting;
+    /// assert_eq!(p.resolve(None, b"new"), Some(b"new".to_vec()));
+    /// assert_eq!(p.resolve(Some(b"old"), b"new"), None);
+    /// ```
+    pub fn resolve(&self, existing: Option<&[u8]>, upserted: &[u8]) -> Option<Vec<u8>> {
+        let existing: &[u8] = match existing {
+            None => return Some(upserted.to_vec()),
+            Some(e) => e,
+        };
+        let resolved: Option<Vec<u8>> = match self {
+            Self::Overwrite => Some(upserted.to_vec()),
+            Self::KeepExisting => None,
+            Self::NewerByDateTime(extract) => match (extract(existing), extract(upserted)) {
+                (_, None) => None,
+                (None, Some(_)) => Some(upserted.to_vec()),
+                (Some(e), Some(u)) => (e < u).then(|| upserted.to_vec()),
+            },
+            Self::Merge(merge) => Some(merge(existing, upserted)),
+        };
+        resolved.filter(|v: &Vec<u8>| v.as_slice().ne(existing))
+    }
+}
+
+impl fmt::Debug for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overwrite => f.write_str("Overwrite"),
+            Self::KeepExisting => f.write_str("KeepExisting"),
+            Self::NewerByDateTime(_) => f.write_str("NewerByDateTime"),
+            Self::Merge(_) => f.write_str("Merge"),
+        }
+    }
+}
+
+/// Upserts an item using the policy(read-modify-write).
+///
+/// Returns 0 if nothing saved.
+///
+/// Not atomic: the value may be changed by others between the get and the upsert
+/// (the change will be overwritten). Use a backend specific implementation which locks the row
+/// (e.g, `postgres::upsert_resolved_locked`) if concurrent writers exist.
+///
+/// # Arguments
+/// - store: Gets the existing value, Upserts the resolved value.
+/// - b: Target bucket.
+/// - i: The item to be upserted.
+/// - policy: Decides the value to be saved.
+pub fn upsert_resolved<S>(
+    store: &mut S,
+    b: &Bucket,
+    i: &RawItem,
+    policy: &ConflictPolicy,
+) -> Result<u64, Event>
+where
+    S: GetRaw + UpsertRaw + ?Sized,
+{
+    let key: &[u8] = i.as_key();
+    let existing: Option<Vec<u8>> = store.get(b, key)?;
+    match policy.resolve(existing.as_deref(), i.as_val()) {
+        None => Ok(0),
+        Some(val) => store.upsert(b, &RawItem::new(key.to_vec(), val)),
+    }
+}
+
+/// Upserts an item using a `ConflictPolicy`.
+///
+/// The default implementation uses `upsert_resolved`(read-modify-write) which is not atomic:
+/// concurrent writers may lose updates.
+/// Backends can use a faster way(e.g, `ON CONFLICT DO NOTHING`) for some policies
+/// or lock the row(e.g, `SELECT ... FOR UPDATE` in postgres).
+pub trait ConflictUpsert: GetRaw + UpsertRaw {
+    /// Upserts an item using the policy(returns 0 if nothing saved).
+    fn upsert_with_policy(
+        &mut self,
+        b: &Bucket,
+        i: &RawItem,
+        policy: &ConflictPolicy,
+    ) -> Result<u64, Event> {
+        upsert_resolved(self, b, i, policy)
+    }
+}
+
+/// Creates new upsert which uses the policy.
+///
+/// # Arguments
+/// - store: Upserts an item using the policy.
+/// - policy: Decides the value to be saved.
+pub fn upsert_with_policy_new_func<S>(
+    mut store: S,
+    policy: ConflictPolicy,
+) -> impl FnMut(&Bucket, &RawItem) -> Result<u64, Event>
+where
+    S: ConflictUpsert,
+{
+    move |b: &Bucket, i: &RawItem| store.upsert_with_policy(b, i, &policy)
+}
+
+#[cfg(test)]
+mod test_conflict {
+
+    mod resolve {
+        use crate::datetime::DateTime;
+        use crate::kvstore::conflict::ConflictPolicy;
+
+        fn newer() -> ConflictPolicy {
+            ConflictPolicy::NewerByDateTime(Box::new(|v: &[u8]| {
+                let s: &str = std::str::from_utf8(v).ok()?;
+                DateTime::parse_rfc3339(s.split('|').next()?).ok()
+            }))
+        }
+
+        #[test]
+        fn test_overwrite() {
+            let p = ConflictPolicy::default();
+            assert_eq!(p.resolve(Some(b"old"), b"new"), Some(b"new".to_vec()));
+            assert_eq!(p.resolve(Some(b"same"), b"same"), None);
+        }
+
+        #[test]
+        fn test_newer() {
+            let p: ConflictPolicy = newer();
+            let old: &[u8] = b"2022-11-02T00:30:21Z|corrected";
+            let retransmit: &[u8] = b"2022-11-02T00:30:20Z|raw";
+            let fresh: &[u8] = b"2022-11-02T00:30:22Z|raw";
+            assert_eq!(p.resolve(Some(old), retransmit), None);
+            assert_eq!(p.resolve(Some(old), fresh), Some(fresh.to_vec()));
+            assert_eq!(p.resolve(Some(old), b"broken"), None);
+            assert_eq!(p.resolve(Some(b"broken"), fresh), Some(fresh.to_vec()));
+        }
+
+        #[test]
+        fn test_merge() {
+            let p = ConflictPolicy::Merge(Box::new(|e: &[u8], u: &[u8]| [e, u].join(&b',')));
+            assert_eq!(p.resolve(Some(b"1"), b"2"), Some(b"1,2".to_vec()));
+            assert_eq!(p.resolve(None, b"2"), Some(b"2".to_vec()));
+        }
+    }
+
+    mod upsert_with_policy {
+        use crate::bucket::Bucket;
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::conflict::{self, ConflictPolicy};
+        use crate::kvstore::create::Create;
+        use crate::kvstore::get::GetRaw;
+        use crate::kvstore::mem::MemStore;
+
+        #[test]
+        fn test_keep_existing() {
+            let mut m: MemStore = MemStore::new();
+            let b: Bucket = Bucket::from(String::from("data"));
+            Create::create(&mut m, &b).unwrap();
+            let mut upst =
+                conflict::upsert_with_policy_new_func(&mut m, ConflictPolicy::KeepExisting);
+            let first: RawItem = Item::new(b"k".to_vec(), b"first".to_vec());
+            let second: RawItem = Item::new(b"k".to_vec(), b"second".to_vec());
+            assert_eq!(upst(&b, &first).unwrap(), 1);
+            assert_eq!(upst(&b, &second).unwrap(), 0);
+            drop(upst);
+            assert_eq!(
+                GetRaw::get(&mut m, &b, b"k").unwrap(),
+                Some(b"first".to_vec())
+            );
+        }
+    }
+}
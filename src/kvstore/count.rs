@@ -4,6 +4,7 @@ use std::path::Path;
 
 use std::collections::BTreeMap;
 
+use crate::namer::BucketNamer;
 use crate::{
     bucket::Bucket, count::Count, date::Date, datetime::DateTime, device::Device, evt::Event,
 };
@@ -31,6 +32,57 @@ where
     Ok(Count::new(cnt, dt))
 }
 
+/// Counts number of rows in a data bucket named by the namer.
+///
+/// # Arguments
+/// - counter: Counts number of rows in a bucket.
+/// - dev: Target device.
+/// - date: Target date.
+/// - time_source: Gets current date/time.
+/// - namer: Creates the data bucket name.
+pub fn count_data_bucket4date_with_namer<C, T, N>(
+    counter: &mut C,
+    dev: &Device,
+    date: &Date,
+    time_source: &T,
+    namer: &N,
+) -> Result<Count, Event>
+where
+    C: FnMut(&Bucket) -> Result<u64, Event>,
+    T: Fn() -> Result<DateTime, Event>,
+    N: BucketNamer,
+{
+    let b: Bucket = namer.data(dev, date);
+    let cnt: u64 = counter(&b)?;
+    let dt: DateTime = time_source()?;
+    Ok(Count::new(cnt, dt))
+}
+
+/// Counts number of rows in data buckets of the dates.
+///
+/// # Arguments
+/// - counter: Counts number of rows in a bucket.
+/// - dev: Target device.
+/// - dates: Target dates(e.g, `DateRange`).
+/// - time_source: Gets current date/time.
+pub fn count_data_bucket4range<C, T, R>(
+    counter: &mut C,
+    dev: &Device,
+    dates: R,
+    time_source: &T,
+) -> Result<Vec<(Date, Count)>, Event>
+where
+    C: FnMut(&Bucket) -> Result<u64, Event>,
+    T: Fn() -> Result<DateTime, Event>,
+    R: Iterator<Item = Date>,
+{
+    dates
+        .map(|date: Date| {
+            count_data_bucket4date(counter, dev, &date, time_source).map(|c: Count| (date, c))
+        })
+        .collect()
+}
+
 /// Creates new counter which counts number of rows of a data bucket.
 ///
 /// # Arguments
@@ -60,11 +112,15 @@ where
     count_data_bucket4date_new(counter, DateTime::time_source_new_std())
 }
 
+/// Reads/writes cached counts.
+///
+/// `read` must return `Event::NotCached` if the count is not cached yet.
 pub trait Cache {
     fn read(&mut self, b: &Bucket) -> Result<Count, Event>;
     fn write(&mut self, b: &Bucket, c: &Count) -> Result<(), Event>;
 }
 
+/// Counts number of rows in a bucket.
 pub trait Counter {
     fn count(&mut self, b: &Bucket) -> Result<Count, Event>;
 }
@@ -122,12 +178,47 @@ where
     F: Cache,
     S: Counter,
 {
+    /// Counts using the slow counter only if not cached(other errors will be returned).
     fn count(&mut self, b: &Bucket) -> Result<Count, Event> {
-        self.fast.read(b).or_else(|_| {
-            let cnt: Count = self.slow.count(b)?;
-            self.fast.write(b, &cnt)?;
+        count_cached_shared(
+            self,
+            b,
+            |s: &mut Self, b: &Bucket| s.fast.read(b),
+            |s: &mut Self, b: &Bucket| s.slow.count(b),
+            |s: &mut Self, b: &Bucket, c: &Count| s.fast.write(b, c),
+        )
+    }
+}
+
+/// Reads a cached count or counts and caches it if not cached(`Event::NotCached`).
+///
+/// Other cache errors will be returned.
+///
+/// # Arguments
+/// - shared: Shared resource used by read/count/write(e.g, a store which caches its counts).
+/// - b: Target bucket.
+/// - read: Reads the cached count.
+/// - count: Counts number of rows in the bucket.
+/// - write: Caches the count.
+pub(crate) fn count_cached_shared<T, R, C, W>(
+    shared: &mut T,
+    b: &Bucket,
+    read: R,
+    count: C,
+    write: W,
+) -> Result<Count, Event>
+where
+    R: Fn(&mut T, &Bucket) -> Result<Count, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<Count, Event>,
+    W: Fn(&mut T, &Bucket, &Count) -> Result<(), Event>,
+{
+    match read(shared, b) {
+        Err(Event::NotCached(_)) => {
+            let cnt: Count = count(shared, b)?;
+            write(shared, b, &cnt)?;
             Ok(cnt)
-        })
+        }
+        cached => cached,
     }
 }
 
@@ -154,7 +245,7 @@ pub fn cache_new_std_btree_map() -> impl Cache {
     let read = |m: &mut BTreeMap<Bucket, Count>, b: &Bucket| {
         m.get(b)
             .copied()
-            .ok_or_else(|| Event::UnexpectedError(String::from("No entry")))
+            .ok_or_else(|| Event::NotCached(b.as_str().into()))
     };
     let write = move |m: &mut BTreeMap<Bucket, Count>, b: &Bucket, c: &Count| match m.get_mut(b) {
         Some(cnt) => {
@@ -319,3 +410,55 @@ where
         Ok(Count::from(u))
     }
 }
+
+#[cfg(test)]
+mod test_count {
+
+    mod counter_cached_new {
+        use crate::bucket::Bucket;
+        use crate::count::Count;
+        use crate::datetime::DateTime;
+        use crate::evt::Event;
+        use crate::kvstore::count::{self, Counter};
+
+        fn slow() -> impl Counter {
+            count::counter_new_from_func(|_: &Bucket| Ok(Count::new(42, DateTime::default())))
+        }
+
+        #[test]
+        fn test_cache_miss() {
+            let mut c = count::counter_cached_new_default_std(slow());
+            let b: Bucket = Bucket::from(String::from("data"));
+            assert_eq!(c.count(&b).unwrap().as_count(), 42);
+        }
+
+        #[test]
+        fn test_cache_error() {
+            let cache = count::cache_new_shared(
+                (),
+                |_: &mut (), b: &Bucket| Err(Event::Timeout(b.as_str().into())),
+                |_: &mut (), _: &Bucket, _: &Count| Ok(()),
+            );
+            let mut c = count::counter_cached_new(cache, slow());
+            let b: Bucket = Bucket::from(String::from("data"));
+            assert!(matches!(c.count(&b), Err(Event::Timeout(_))));
+        }
+    }
+
+    mod count_data_bucket4range {
+        use crate::datetime::DateTime;
+        use crate::kvstore::{count, mem};
+        use crate::{bucket::Bucket, device::Device};
+
+        #[test]
+        fn test_range() {
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let (m, range) = mem::range_fixture(&dev);
+            let mut c = |b: &Bucket| Ok(m.count(b).unwrap_or(0));
+            let time_source = || Ok(DateTime::from_unixtime_us(0));
+            let counts = count::count_data_bucket4range(&mut c, &dev, range, &time_source).unwrap();
+            let cnts: Vec<u64> = counts.iter().map(|(_, c)| c.as_count()).collect();
+            assert_eq!(cnts, vec![1, 0, 1]);
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
 use std::ops::DerefMut;
 use std::sync::Mutex;
 
+use crate::namer::BucketNamer;
 use crate::remove::{
-    is_delete_target, is_delete_target_device, is_drop_target, is_drop_target_device,
-    is_drop_target_stale,
+    is_delete_target, is_delete_target_device, is_delete_target_device_with_namer,
+    is_delete_target_with_namer, is_drop_target, is_drop_target_device,
+    is_drop_target_device_with_namer, is_drop_target_stale, is_drop_target_stale_with_namer,
 };
 use crate::{bucket::Bucket, date::Date, device::Device, evt::Event};
 
@@ -23,6 +26,17 @@ pub trait DeleteRow {
     fn finalize(self) -> Result<(), Event>;
 }
 
+/// Deletes rows in a key range from the bucket.
+pub trait DeleteRange {
+    /// Deletes rows whose keys are in `lower..upper`(bytewise order).
+    ///
+    /// # Arguments
+    /// - b: Target bucket.
+    /// - lower: Lower bound(inclusive). Empty lower bound means the first key.
+    /// - upper: Upper bound(exclusive).
+    fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event>;
+}
+
 /// Removes a device.
 ///
 /// # Arguments
@@ -81,6 +95,29 @@ where
     )
 }
 
+/// Removes a device from buckets named by the namer.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - target: The device to be removed.
+/// - namer: Parses bucket names.
+pub fn delete_device_with_namer<D, N>(
+    drop_del_list: D,
+    target: Device,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+    N: BucketNamer,
+{
+    delete_device(
+        drop_del_list,
+        &|b: &Bucket, d: &Device| is_drop_target_device_with_namer(namer, b, d),
+        &|b: &Bucket| is_delete_target_device_with_namer(namer, b),
+        target,
+    )
+}
+
 /// Drops stale buckets and deletes stale rows from buckets.
 ///
 /// # Arguments
@@ -130,6 +167,329 @@ where
     delete_stale_data(drop_del_list, &is_drop_target_stale, &is_delete_target, lbi)
 }
 
+/// Drops stale buckets and deletes all stale rows(keys before lbi) from buckets.
+///
+/// Unlike `delete_stale_data`, every date key older than lbi will be deleted.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_range<D, T, R>(
+    mut drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: Date,
+) -> Result<u64, Event>
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    let vb: Vec<Bucket> = drop_del_list.list()?;
+    let drop_cnt: u64 = vb.iter().try_fold(0, |tot, b| {
+        let tgt: bool = drop_target(b, &lbi);
+        match tgt {
+            true => drop_del_list.drop(b).map(|cnt| cnt + tot),
+            false => Ok(tot),
+        }
+    })?;
+    let del_cnt: u64 = vb.iter().try_fold(0, |tot, b| {
+        let tgt: bool = remove_target(b);
+        match tgt {
+            true => drop_del_list
+                .delete_range(b, &[], lbi.as_bytes())
+                .map(|cnt| cnt + tot),
+            false => Ok(tot),
+        }
+    })?;
+    drop_del_list.finalize()?;
+    Ok(drop_cnt + del_cnt)
+}
+
+/// Drops stale buckets and deletes all stale rows which uses default checkers.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_range_default<D>(drop_del_list: D, lbi: Date) -> Result<u64, Event>
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets,
+{
+    delete_stale_data_range(drop_del_list, &is_drop_target_stale, &is_delete_target, lbi)
+}
+
+/// Drops stale buckets and deletes stale rows from buckets named by the namer.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+/// - namer: Parses bucket names.
+pub fn delete_stale_data_with_namer<D, N>(
+    drop_del_list: D,
+    lbi: Date,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+    N: BucketNamer,
+{
+    delete_stale_data(
+        drop_del_list,
+        &|b: &Bucket, d: &Date| is_drop_target_stale_with_namer(namer, b, d),
+        &|b: &Bucket| is_delete_target_with_namer(namer, b),
+        lbi,
+    )
+}
+
+/// Detailed result of a removal.
+///
+/// On error, changes are not finalized(a transactional backend rolls them back):
+/// drops/deletes executed before the error are reported as attempted, not as dropped/deleted.
+#[derive(Debug, Default)]
+pub struct RemovalReport {
+    dropped: Vec<Bucket>,
+    deleted: BTreeMap<Bucket, u64>,
+    attempted_drops: Vec<Bucket>,
+    attempted_deletes: BTreeMap<Bucket, u64>,
+    skipped: Vec<Bucket>,
+    failed: Option<Bucket>,
+    error: Option<Event>,
+}
+
+impl RemovalReport {
+    /// Gets dropped buckets(empty on error).
+    pub fn dropped(&self) -> &[Bucket] {
+        &self.dropped
+    }
+
+    /// Gets number of deleted rows per bucket(empty on error).
+    pub fn deleted(&self) -> &BTreeMap<Bucket, u64> {
+        &self.deleted
+    }
+
+    /// Gets buckets dropped before the error(may be rolled back).
+    pub fn attempted_drops(&self) -> &[Bucket] {
+        &self.attempted_drops
+    }
+
+    /// Gets number of rows deleted before the error per bucket(may be rolled back).
+    pub fn attempted_deletes(&self) -> &BTreeMap<Bucket, u64> {
+        &self.attempted_deletes
+    }
+
+    /// Gets buckets which are neither dropped nor modified.
+    pub fn skipped(&self) -> &[Bucket] {
+        &self.skipped
+    }
+
+    /// Gets the bucket which caused the error(`None` if listing/finalization failed).
+    pub fn failed(&self) -> Option<&Bucket> {
+        self.failed.as_ref()
+    }
+
+    /// Gets the first error.
+    pub fn error(&self) -> Option<&Event> {
+        self.error.as_ref()
+    }
+
+    /// Checks if the removal completed without error.
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Gets number of dropped buckets plus number of deleted rows(0 on error).
+    pub fn total(&self) -> u64 {
+        self.dropped.len() as u64 + self.deleted.values().sum::<u64>()
+    }
+
+    fn fail(mut self, b: Option<&Bucket>, e: Event) -> Self {
+        self.attempted_drops = std::mem::take(&mut self.dropped);
+        self.attempted_deletes = std::mem::take(&mut self.deleted);
+        self.failed = b.cloned();
+        self.error = Some(e);
+        self
+    }
+}
+
+/// Removes buckets/rows and reports each action.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - drop_target: Checks if the bucket must be dropped.
+/// - remove_target: Checks if the bucket can have rows to be deleted.
+/// - before_drop: Called before each drop(the drop will be skipped on error).
+/// - delete: Deletes rows from the bucket.
+pub(crate) fn remove_report<D, T, R, P, X>(
+    mut drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    mut before_drop: P,
+    mut delete: X,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+    T: Fn(&Bucket) -> bool,
+    R: Fn(&Bucket) -> bool,
+    P: FnMut(&mut D, &Bucket) -> Result<(), Event>,
+    X: FnMut(&mut D, &Bucket) -> Result<u64, Event>,
+{
+    let mut report = RemovalReport::default();
+    let vb: Vec<Bucket> = match drop_del_list.list() {
+        Ok(vb) => vb,
+        Err(e) => return report.fail(None, e),
+    };
+    for b in vb.iter().filter(|b| drop_target(b)) {
+        let dropped = before_drop(&mut drop_del_list, b).and_then(|_| drop_del_list.drop(b));
+        match dropped {
+            Ok(_) => report.dropped.push(b.clone()),
+            Err(e) => return report.fail(Some(b), e),
+        }
+    }
+    for b in vb.iter().filter(|b| !drop_target(b)) {
+        match remove_target(b) {
+            false => report.skipped.push(b.clone()),
+            true => match delete(&mut drop_del_list, b) {
+                Ok(cnt) => {
+                    report.deleted.insert(b.clone(), cnt);
+                }
+                Err(e) => return report.fail(Some(b), e),
+            },
+        }
+    }
+    match drop_del_list.finalize() {
+        Ok(_) => report,
+        Err(e) => report.fail(None, e),
+    }
+}
+
+/// Removes a device and reports each action.
+///
+/// Stops at the first error(changes are not finalized).
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - drop_target: Checks if the bucket is drop target.
+/// - remove_target: Checks if the bucket can have delete target row.
+/// - target: The device to be removed.
+pub fn delete_device_report<D, T, R>(
+    drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    target: Device,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+    T: Fn(&Bucket, &Device) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    remove_report(
+        drop_del_list,
+        &|b: &Bucket| drop_target(b, &target),
+        remove_target,
+        |_: &mut D, _: &Bucket| Ok(()),
+        |d: &mut D, b: &Bucket| d.delete(b, target.as_bytes()),
+    )
+}
+
+/// Removes a device using default checkers and reports each action.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - target: The device to be removed.
+pub fn delete_device_default_report<D>(drop_del_list: D, target: Device) -> RemovalReport
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+{
+    delete_device_report(
+        drop_del_list,
+        &is_drop_target_device,
+        &is_delete_target_device,
+        target,
+    )
+}
+
+/// Drops stale buckets, deletes stale rows and reports each action.
+///
+/// Same as `delete_stale_data`(only the row whose key equals lbi will be deleted).
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_report<D, T, R>(
+    drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: Date,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    remove_report(
+        drop_del_list,
+        &|b: &Bucket| drop_target(b, &lbi),
+        remove_target,
+        |_: &mut D, _: &Bucket| Ok(()),
+        |d: &mut D, b: &Bucket| d.delete(b, lbi.as_bytes()),
+    )
+}
+
+/// Drops stale buckets, deletes stale rows using default checkers and reports each action.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_default_report<D>(drop_del_list: D, lbi: Date) -> RemovalReport
+where
+    D: DropBucket + DeleteRow + ListBuckets,
+{
+    delete_stale_data_report(drop_del_list, &is_drop_target_stale, &is_delete_target, lbi)
+}
+
+/// Drops stale buckets, deletes all stale rows(keys before lbi) and reports each action.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_range_report<D, T, R>(
+    drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: Date,
+) -> RemovalReport
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    remove_report(
+        drop_del_list,
+        &|b: &Bucket| drop_target(b, &lbi),
+        remove_target,
+        |_: &mut D, _: &Bucket| Ok(()),
+        |d: &mut D, b: &Bucket| d.delete_range(b, &[], lbi.as_bytes()),
+    )
+}
+
+/// Drops stale buckets, deletes all stale rows using default checkers and reports each action.
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data_range_default_report<D>(drop_del_list: D, lbi: Date) -> RemovalReport
+where
+    D: DropBucket + DeleteRange + DeleteRow + ListBuckets,
+{
+    delete_stale_data_range_report(drop_del_list, &is_drop_target_stale, &is_delete_target, lbi)
+}
+
 /// Drops buckets and deletes rows which contains the device info.
 ///
 /// # Arguments
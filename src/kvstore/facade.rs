@@ -0,0 +1,377 @@
+//! High level store which combines the key/value store traits.
+
+use crate::item::RawItem;
+use crate::{
+    bucket::Bucket, count::Count, data::RawData, date::Date, datetime::DateTime, device::Device,
+    evt::Event,
+};
+
+use crate::kvstore::count::{self, Cache, Counter};
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{self, DeleteRow, DropBucket};
+use crate::kvstore::get::{self, GetRaw};
+use crate::kvstore::list::{self, ListBuckets, ListKeys};
+use crate::kvstore::transaction::{self, Transactional};
+use crate::kvstore::upsert::{self, BatchUpsert, UpsertRaw};
+
+/// Key/value store which supports all basic operations.
+///
+/// Implemented for every type which implements the basic traits.
+/// Optional capabilities(e.g, `BatchUpsert`, `Cache`) are required by some methods of `SpacetimeDb`.
+pub trait KvStore:
+    Create + UpsertRaw + GetRaw + ListBuckets + ListKeys<Vec<u8>> + DropBucket + DeleteRow
+{
+}
+
+impl<S> KvStore for S where
+    S: Create + UpsertRaw + GetRaw + ListBuckets + ListKeys<Vec<u8>> + DropBucket + DeleteRow
+{
+}
+
+/// Borrowed store which does not finalize(the owner finalizes).
+pub(crate) struct StoreRef<'a, S> {
+    store: &'a mut S,
+}
+
+impl<'a, S> StoreRef<'a, S> {
+    pub(crate) fn new(store: &'a mut S) -> Self {
+        Self { store }
+    }
+}
+
+impl<S> GetRaw for StoreRef<'_, S>
+where
+    S: GetRaw,
+{
+    fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        self.store.get(b, key)
+    }
+
+    fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        self.store.chk(b)
+    }
+}
+
+impl<S> ListBuckets for StoreRef<'_, S>
+where
+    S: ListBuckets,
+{
+    fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        self.store.list()
+    }
+}
+
+impl<S> DropBucket for StoreRef<'_, S>
+where
+    S: DropBucket,
+{
+    fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        self.store.drop(b)
+    }
+}
+
+impl<S> DeleteRow for StoreRef<'_, S>
+where
+    S: DeleteRow,
+{
+    fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        self.store.delete(b, key)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+/// Device/date oriented store built on a `KvStore`.
+///
+/// Changes are finalized by `finalize`(e.g, commit).
+#[derive(Debug, Default, Clone)]
+pub struct SpacetimeDb<S> {
+    store: S,
+}
+
+impl<S> SpacetimeDb<S>
+where
+    S: KvStore,
+{
+    /// Creates new db which uses the store.
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    fn borrowed(&mut self) -> StoreRef<'_, S> {
+        StoreRef::new(&mut self.store)
+    }
+
+    /// Gets the underlying store.
+    pub fn as_store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Converts into the underlying store(without finalization).
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// Finalizes the underlying store.
+    pub fn finalize(self) -> Result<(), Event> {
+        UpsertRaw::finalize(self.store)
+    }
+
+    /// Upserts data(data buckets and master buckets).
+    ///
+    /// Duplicates will be ignored.
+    ///
+    /// # Arguments
+    /// - source: `RawData` source iterator.
+    pub fn upsert<I>(&mut self, source: I) -> Result<u64, Event>
+    where
+        I: Iterator<Item = RawData>,
+    {
+        let mut cu = |b: &Bucket, i: &RawItem| upsert::create_upsert(&mut self.store, b, i);
+        upsert::upsert_all(source, &mut cu)
+    }
+
+    /// Upserts data per bucket using `BatchUpsert`.
+    ///
+    /// # Arguments
+    /// - source: `RawData` source iterator.
+    pub fn upsert_batch<I>(&mut self, source: I) -> Result<u64, Event>
+    where
+        I: Iterator<Item = RawData>,
+        S: BatchUpsert,
+    {
+        let mut cu =
+            |b: &Bucket, items: &[RawItem]| upsert::create_upsert_batch(&mut self.store, b, items);
+        let gen = upsert::upsert_value_generator_new_func_default();
+        upsert::upsert_all_batch_ex(source, &mut cu, gen)
+    }
+
+    /// Tries to get an item from the data bucket(missing bucket will be ignored).
+    ///
+    /// # Arguments
+    /// - dev: Target device.
+    /// - date: Target date.
+    /// - key: Bytes key.
+    pub fn get(&mut self, dev: &Device, date: &Date, key: &[u8]) -> Result<Option<RawItem>, Event> {
+        let getter = get::get_raw_ignore_missing_bucket_new_func(self.borrowed());
+        let mut get_data = get::get_raw_new(getter);
+        get_data(dev, date, key)
+    }
+
+    /// Gets all keys from the data bucket.
+    ///
+    /// # Arguments
+    /// - dev: Target device.
+    /// - date: Target date.
+    pub fn list_keys(&mut self, dev: &Device, date: &Date) -> Result<Vec<Vec<u8>>, Event> {
+        let mut lst = |b: &Bucket| ListKeys::list(&mut self.store, b);
+        list::list_keys4data(&mut lst, date, dev)
+    }
+
+    /// Counts number of rows in the data bucket using `Counter`.
+    ///
+    /// # Arguments
+    /// - dev: Target device.
+    /// - date: Target date.
+    pub fn count(&mut self, dev: &Device, date: &Date) -> Result<Count, Event>
+    where
+        S: Counter,
+    {
+        let b: Bucket = Bucket::new_data_bucket(dev, date);
+        Counter::count(&mut self.store, &b)
+    }
+
+    /// Counts number of rows in the data bucket by getting all keys.
+    ///
+    /// Can be used if the store does not implement `Counter`.
+    ///
+    /// # Arguments
+    /// - dev: Target device.
+    /// - date: Target date.
+    pub fn count_keys(&mut self, dev: &Device, date: &Date) -> Result<Count, Event> {
+        let mut counter = |b: &Bucket| {
+            let keys: Vec<Vec<u8>> = ListKeys::list(&mut self.store, b)?;
+            Ok(keys.len() as u64)
+        };
+        let time_source = DateTime::time_source_new_std();
+        count::count_data_bucket4date(&mut counter, dev, date, &time_source)
+    }
+
+    /// Counts number of rows in the data bucket using cached counts.
+    ///
+    /// The count will be cached if not cached yet(`Event::NotCached`).
+    /// Other cache errors will be returned.
+    ///
+    /// # Arguments
+    /// - dev: Target device.
+    /// - date: Target date.
+    pub fn count_cached(&mut self, dev: &Device, date: &Date) -> Result<Count, Event>
+    where
+        S: Cache + Counter,
+    {
+        let b: Bucket = Bucket::new_data_bucket(dev, date);
+        count::count_cached_shared(
+            &mut self.store,
+            &b,
+            |s: &mut S, b: &Bucket| s.read(b),
+            |s: &mut S, b: &Bucket| Counter::count(s, b),
+            |s: &mut S, b: &Bucket, c: &Count| s.write(b, c),
+        )
+    }
+
+    /// Removes a device(drops data buckets, deletes master rows).
+    ///
+    /// # Arguments
+    /// - target: The device to be removed.
+    pub fn remove_device(&mut self, target: Device) -> Result<u64, Event> {
+        delete::delete_device_default(self.borrowed(), target)
+    }
+
+    /// Drops stale buckets and deletes stale rows.
+    ///
+    /// # Arguments
+    /// - lbi: Date threshold(fresh date lower bound, inclusive).
+    pub fn remove_stale(&mut self, lbi: Date) -> Result<u64, Event> {
+        delete::delete_stale_data_default(self.borrowed(), lbi)
+    }
+
+    /// Runs operations in a transaction(rolls back on error).
+    ///
+    /// # Arguments
+    /// - f: Operations using this db.
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, Event>
+    where
+        S: Transactional,
+        F: FnOnce(&mut Self) -> Result<T, Event>,
+    {
+        transaction::transaction(self, f)
+    }
+}
+
+impl<S> Transactional for SpacetimeDb<S>
+where
+    S: Transactional,
+{
+    fn begin(&mut self) -> Result<(), Event> {
+        self.store.begin()
+    }
+
+    fn commit(&mut self) -> Result<(), Event> {
+        self.store.commit()
+    }
+
+    fn rollback(&mut self) -> Result<(), Event> {
+        self.store.rollback()
+    }
+}
+
+#[cfg(test)]
+mod test_facade {
+
+    mod spacetime_db {
+        use crate::item::Item;
+        use crate::kvstore::delete;
+        use crate::kvstore::facade::SpacetimeDb;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, data::RawData, date::Date, device::Device};
+
+        const DEV: &str = "cafef00ddeadbeafface864299792458";
+
+        fn source() -> impl Iterator<Item = RawData> {
+            let keys: [&[u8]; 3] = [b"00:30:21.0Z", b"00:30:22.0Z", b"00:30:21.0Z"];
+            ["2022_11_01", "2022_11_02"]
+                .into_iter()
+                .flat_map(move |d: &str| {
+                    keys.into_iter().map(move |k: &[u8]| {
+                        Data::new(
+                            Device::new_unchecked(DEV.into()),
+                            Date::new_unchecked(d.into()),
+                            Item::new(k.to_vec(), b"42".to_vec()),
+                        )
+                    })
+                })
+        }
+
+        fn dev() -> Device {
+            Device::new_unchecked(DEV.into())
+        }
+
+        fn date(s: &str) -> Date {
+            Date::new_unchecked(s.into())
+        }
+
+        #[test]
+        fn test_upsert_get() {
+            let mut db: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            db.upsert(source()).unwrap();
+
+            let mut m: MemStore = MemStore::new();
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source(), &mut cu).unwrap();
+            assert_eq!(db.as_store_mut(), &m);
+
+            let found = db.get(&dev(), &date("2022_11_01"), b"00:30:22.0Z").unwrap();
+            assert_eq!(found.map(|i| i.into_pair().1), Some(b"42".to_vec()));
+            let missing = db.get(&dev(), &date("2022_11_03"), b"00:30:22.0Z").unwrap();
+            assert!(missing.is_none());
+
+            let keys: Vec<Vec<u8>> = db.list_keys(&dev(), &date("2022_11_02")).unwrap();
+            assert_eq!(keys, vec![b"00:30:21.0Z".to_vec(), b"00:30:22.0Z".to_vec()]);
+            db.finalize().unwrap();
+        }
+
+        #[test]
+        fn test_upsert_batch() {
+            let mut db: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            db.upsert_batch(source()).unwrap();
+            let mut expected: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            expected.upsert(source()).unwrap();
+            assert_eq!(db.into_inner(), expected.into_inner());
+        }
+
+        #[test]
+        fn test_count() {
+            let mut db: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            db.upsert(source()).unwrap();
+            let d: Date = date("2022_11_01");
+            assert_eq!(db.count(&dev(), &d).unwrap().as_count(), 2);
+            assert_eq!(db.count_keys(&dev(), &d).unwrap().as_count(), 2);
+
+            assert_eq!(db.count_cached(&dev(), &d).unwrap().as_count(), 2);
+            db.upsert(
+                [Data::new(
+                    dev(),
+                    d.clone(),
+                    Item::new(b"k".to_vec(), vec![]),
+                )]
+                .into_iter(),
+            )
+            .unwrap();
+            assert_eq!(db.count(&dev(), &d).unwrap().as_count(), 3);
+            assert_eq!(db.count_cached(&dev(), &d).unwrap().as_count(), 2);
+        }
+
+        #[test]
+        fn test_remove() {
+            let mut db: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            db.upsert(source()).unwrap();
+            let mut m: MemStore = db.as_store_mut().clone();
+
+            let removed: u64 = db.remove_stale(date("2022_11_02")).unwrap();
+            let expected: u64 =
+                delete::delete_stale_data_default(&mut m, date("2022_11_02")).unwrap();
+            assert_eq!(removed, expected);
+            assert_eq!(db.as_store_mut(), &m);
+            assert!(db.list_keys(&dev(), &date("2022_11_01")).is_err());
+
+            assert!(0 < db.remove_device(dev()).unwrap());
+            assert!(db
+                .get(&dev(), &date("2022_11_02"), b"00:30:21.0Z")
+                .unwrap()
+                .is_none());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Checks consistency between master buckets and data buckets.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bucket::{Bucket, BucketKind};
+use crate::namer::{BucketNamer, SimpleBucketNamer};
+use crate::{date::Date, device::Device, evt::Event};
+
+use crate::kvstore::list::{ListBuckets, ListKeys};
+
+/// A row(key) of a master bucket.
+pub type MasterRow = (Bucket, Vec<u8>);
+
+/// Inconsistencies found by `fsck`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsckReport {
+    orphans: Vec<Bucket>,
+    dangling: Vec<MasterRow>,
+    missing: Vec<MasterRow>,
+    invalid: Vec<Bucket>,
+}
+
+impl FsckReport {
+    /// Gets data buckets which are not referred by the masters for the device/date.
+    pub fn orphans(&self) -> &[Bucket] {
+        &self.orphans
+    }
+
+    /// Gets master rows which refer to no data bucket.
+    pub fn dangling(&self) -> &[MasterRow] {
+        &self.dangling
+    }
+
+    /// Gets master rows which must exist for data buckets(but not found).
+    pub fn missing(&self) -> &[MasterRow] {
+        &self.missing
+    }
+
+    /// Gets buckets which have a known prefix but can not be parsed.
+    pub fn invalid(&self) -> &[Bucket] {
+        &self.invalid
+    }
+
+    /// Checks if no inconsistency found.
+    pub fn is_consistent(&self) -> bool {
+        self.orphans.is_empty()
+            && self.dangling.is_empty()
+            && self.missing.is_empty()
+            && self.invalid.is_empty()
+    }
+}
+
+/// Checks master buckets and data buckets named by the namer.
+///
+/// Master rows are expected to use dates/devices as keys(like the default value generator).
+///
+/// # Arguments
+/// - store: Gets list of buckets and keys.
+/// - namer: Parses/creates bucket names.
+pub fn fsck_with_namer<S, N>(store: &mut S, namer: &N) -> Result<FsckReport, Event>
+where
+    S: ListBuckets + ListKeys<Vec<u8>>,
+    N: BucketNamer,
+{
+    let buckets: Vec<Bucket> = ListBuckets::list(store)?;
+
+    let mut report: FsckReport = FsckReport::default();
+    let mut data: BTreeMap<(Date, Device), Bucket> = BTreeMap::new();
+    let mut masters: BTreeMap<Bucket, BucketKind> = BTreeMap::new();
+    for b in buckets {
+        match namer.parse(&b) {
+            Err(_) => report.invalid.push(b),
+            Ok(BucketKind::Unknown) => {}
+            Ok(BucketKind::Data { date, device }) => {
+                data.insert((date, device), b);
+            }
+            Ok(kind) => {
+                masters.insert(b, kind);
+            }
+        }
+    }
+
+    let mut rows: BTreeMap<Bucket, BTreeSet<Vec<u8>>> = BTreeMap::new();
+    for b in masters.keys() {
+        let keys: Vec<Vec<u8>> = ListKeys::list(store, b)?;
+        rows.insert(b.clone(), BTreeSet::from_iter(keys));
+    }
+    let has_row = |b: &Bucket, key: &[u8]| -> bool {
+        rows.get(b)
+            .map(|keys: &BTreeSet<Vec<u8>>| keys.contains(key))
+            .unwrap_or(false)
+    };
+
+    for ((date, device), b) in data.iter() {
+        let dates4device: Bucket = namer.dates4device(device);
+        let devices4date: Bucket = namer.devices4date(date);
+        let expected: Vec<MasterRow> = vec![
+            (dates4device.clone(), date.as_bytes().to_vec()),
+            (devices4date.clone(), device.as_bytes().to_vec()),
+            (namer.dates(), date.as_bytes().to_vec()),
+            (namer.devices(), device.as_bytes().to_vec()),
+        ];
+        let referred: bool =
+            has_row(&dates4device, date.as_bytes()) || has_row(&devices4date, device.as_bytes());
+        if !referred {
+            report.orphans.push(b.clone());
+        }
+        let missing = expected.into_iter().filter(|(mb, k)| !has_row(mb, k));
+        report.missing.extend(missing);
+    }
+
+    let dates: BTreeSet<&[u8]> = data.keys().map(|(d, _)| d.as_bytes()).collect();
+    let devices: BTreeSet<&[u8]> = data.keys().map(|(_, d)| d.as_bytes()).collect();
+    let pairs: BTreeSet<(&[u8], &[u8])> = data
+        .keys()
+        .map(|(date, dev)| (date.as_bytes(), dev.as_bytes()))
+        .collect();
+    for (b, kind) in masters.iter() {
+        let keys = rows.get(b).into_iter().flat_map(|keys| keys.iter());
+        for key in keys {
+            let k: &[u8] = key.as_slice();
+            let found: bool = match kind {
+                BucketKind::DatesForDevice(dev) => pairs.contains(&(k, dev.as_bytes())),
+                BucketKind::DevicesForDate(date) => pairs.contains(&(date.as_bytes(), k)),
+                BucketKind::Dates => dates.contains(k),
+                BucketKind::Devices => devices.contains(k),
+                BucketKind::Data { .. } | BucketKind::Unknown => true,
+            };
+            if !found {
+                report.dangling.push((b.clone(), key.clone()));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks master buckets and data buckets using the default namer.
+///
+/// # Arguments
+/// - store: Gets list of buckets and keys.
+pub fn fsck<S>(store: &mut S) -> Result<FsckReport, Event>
+where
+    S: ListBuckets + ListKeys<Vec<u8>>,
+{
+    fsck_with_namer(store, &SimpleBucketNamer::default())
+}
+
+#[cfg(test)]
+mod test_fsck {
+
+    mod fsck {
+        use crate::item::Item;
+        use crate::kvstore::create::Create;
+        use crate::kvstore::delete::{DeleteRow, DropBucket};
+        use crate::kvstore::fsck::{self, FsckReport};
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        const DEV: &str = "cafef00ddeadbeafface864299792458";
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let source = ["2022_11_01", "2022_11_02"].into_iter().map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked(DEV.into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            });
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source, &mut cu).unwrap();
+            m
+        }
+
+        #[test]
+        fn test_consistent() {
+            let mut m: MemStore = store();
+            let r: FsckReport = fsck::fsck(&mut m).unwrap();
+            assert!(r.is_consistent());
+        }
+
+        #[test]
+        fn test_dropped_data() {
+            let mut m: MemStore = store();
+            let b: Bucket = Bucket::from(format!("data_2022_11_01_{}", DEV));
+            DropBucket::drop(&mut m, &b).unwrap();
+            let r: FsckReport = fsck::fsck(&mut m).unwrap();
+            assert!(r.orphans().is_empty());
+            assert!(r.missing().is_empty());
+            assert_eq!(
+                r.dangling(),
+                &[
+                    (Bucket::new_dates_master(), b"2022_11_01".to_vec()),
+                    (
+                        Bucket::from(format!("dates_{}", DEV)),
+                        b"2022_11_01".to_vec()
+                    ),
+                    (Bucket::from(String::from("devices_2022_11_01")), DEV.into()),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_orphan() {
+            let mut m: MemStore = store();
+            let dates4device: Bucket = Bucket::from(format!("dates_{}", DEV));
+            DeleteRow::delete(&mut m, &dates4device, b"2022_11_02").unwrap();
+            let devices4date: Bucket = Bucket::from(String::from("devices_2022_11_02"));
+            DropBucket::drop(&mut m, &devices4date).unwrap();
+            Create::create(&mut m, &Bucket::from(String::from("data_2022_13_01_x"))).unwrap();
+
+            let r: FsckReport = fsck::fsck(&mut m).unwrap();
+            assert_eq!(
+                r.orphans(),
+                &[Bucket::from(format!("data_2022_11_02_{}", DEV))]
+            );
+            assert_eq!(
+                r.missing(),
+                &[
+                    (dates4device, b"2022_11_02".to_vec()),
+                    (devices4date, DEV.into()),
+                ]
+            );
+            assert!(r.dangling().is_empty());
+            assert_eq!(r.invalid().len(), 1);
+            assert!(!r.is_consistent());
+        }
+    }
+}
@@ -2,6 +2,7 @@ use std::ops::DerefMut;
 use std::sync::Mutex;
 
 use crate::item::{Item, RawItem};
+use crate::namer::BucketNamer;
 use crate::{bucket::Bucket, date::Date, device::Device, evt::Event};
 
 /// Gets all device info from device master bucket.
@@ -177,6 +178,57 @@ where
     get_raw_direct(getter, &b, key)
 }
 
+/// Tries to get a raw item from the data bucket named by the namer.
+///
+/// # Arguments
+/// - getter: Tries to get bytes from the bucket.
+/// - dev:    Target device.
+/// - date:   Target date.
+/// - key:    Bytes key.
+/// - namer:  Creates the data bucket name.
+pub fn get_raw_with_namer<G, N>(
+    getter: &mut G,
+    dev: &Device,
+    date: &Date,
+    key: &[u8],
+    namer: &N,
+) -> Result<Option<RawItem>, Event>
+where
+    G: FnMut(&Bucket, &[u8]) -> Result<Option<Vec<u8>>, Event>,
+    N: BucketNamer,
+{
+    let b: Bucket = namer.data(dev, date);
+    get_raw_direct(getter, &b, key)
+}
+
+/// Tries to get raw items from data buckets of the dates.
+///
+/// Returns found items only.
+///
+/// # Arguments
+/// - getter: Tries to get bytes from the bucket.
+/// - dev:    Target device.
+/// - dates:  Target dates(e.g, `DateRange`).
+/// - key:    Bytes key.
+pub fn get_raw_range<G, R>(
+    getter: &mut G,
+    dev: &Device,
+    dates: R,
+    key: &[u8],
+) -> Result<Vec<(Date, RawItem)>, Event>
+where
+    G: FnMut(&Bucket, &[u8]) -> Result<Option<Vec<u8>>, Event>,
+    R: Iterator<Item = Date>,
+{
+    dates
+        .map(|date: Date| {
+            let o: Option<RawItem> = get_raw(getter, dev, &date, key)?;
+            Ok(o.map(|i: RawItem| (date, i)))
+        })
+        .filter_map(|r: Result<Option<_>, Event>| r.transpose())
+        .collect()
+}
+
 /// Tries to get a raw item from the data bucket which ignores missing bucket.
 ///
 /// # Arguments
@@ -237,3 +289,23 @@ where
         get_raw_ignore_missing_bucket(&mut get, dev, date, key, &mut chk)
     }
 }
+
+#[cfg(test)]
+mod test_get {
+
+    mod get_raw_range {
+        use crate::device::Device;
+        use crate::kvstore::get;
+        use crate::kvstore::mem;
+
+        #[test]
+        fn test_range() {
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let (m, range) = mem::range_fixture(&dev);
+            let mut g = get::get_raw_ignore_missing_bucket_new_func(m);
+            let found = get::get_raw_range(&mut g, &dev, range, b"23:59:59.0Z").unwrap();
+            let dates: Vec<&str> = found.iter().map(|(d, _)| d.as_str()).collect();
+            assert_eq!(dates, vec!["2022_12_31", "2023_01_02"]);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::namer::BucketNamer;
 use crate::{bucket::Bucket, date::Date, device::Device, evt::Event};
 
 /// Gets all buckets.
@@ -10,6 +11,17 @@ pub trait ListKeys<K> {
     fn list(&mut self, b: &Bucket) -> Result<Vec<K>, Event>;
 }
 
+/// Gets keys from a bucket page by page(in key order).
+pub trait ListKeysAfter {
+    /// Gets at most `limit` keys which are greater than `after`(from the first key if None).
+    fn list_after(
+        &mut self,
+        b: &Bucket,
+        after: Option<&[u8]>,
+        limit: u32,
+    ) -> Result<Vec<Vec<u8>>, Event>;
+}
+
 /// Gets all keys from a data bucket.
 ///
 /// # Arguments
@@ -24,6 +36,50 @@ where
     list(&b)
 }
 
+/// Gets all keys from a data bucket named by the namer.
+///
+/// # Arguments
+/// - list: Gets all keys from a bucket.
+/// - date: Target date.
+/// - device: Target device.
+/// - namer: Creates the data bucket name.
+pub fn list_keys4data_with_namer<L, N>(
+    list: &mut L,
+    date: &Date,
+    device: &Device,
+    namer: &N,
+) -> Result<Vec<Vec<u8>>, Event>
+where
+    L: FnMut(&Bucket) -> Result<Vec<Vec<u8>>, Event>,
+    N: BucketNamer,
+{
+    let b: Bucket = namer.data(device, date);
+    list(&b)
+}
+
+/// Keys of a data bucket with its date.
+pub type DatedKeys = (Date, Vec<Vec<u8>>);
+
+/// Gets all keys from data buckets of the dates.
+///
+/// # Arguments
+/// - list: Gets all keys from a bucket.
+/// - dates: Target dates(e.g, `DateRange`).
+/// - device: Target device.
+pub fn list_keys4data_range<L, R>(
+    list: &mut L,
+    dates: R,
+    device: &Device,
+) -> Result<Vec<DatedKeys>, Event>
+where
+    L: FnMut(&Bucket) -> Result<Vec<Vec<u8>>, Event>,
+    R: Iterator<Item = Date>,
+{
+    dates
+        .map(|date: Date| list_keys4data(list, &date, device).map(|keys| (date, keys)))
+        .collect()
+}
+
 /// Creates new list getter which gets all keys from a data bucket.
 ///
 /// Missing bucket will be ignored(returns empty vec).
@@ -49,3 +105,26 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test_list {
+
+    mod list_keys4data_range {
+        use crate::kvstore::list::{self, ListKeys};
+        use crate::kvstore::mem;
+        use crate::{bucket::Bucket, device::Device};
+
+        #[test]
+        fn test_range() {
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let (mut m, range) = mem::range_fixture(&dev);
+            let mut l = |b: &Bucket| match m.count(b).is_ok() {
+                true => ListKeys::list(&mut m, b),
+                false => Ok(vec![]),
+            };
+            let keys = list::list_keys4data_range(&mut l, range, &dev).unwrap();
+            let lens: Vec<usize> = keys.iter().map(|(_, k)| k.len()).collect();
+            assert_eq!(lens, vec![1, 0, 1]);
+        }
+    }
+}
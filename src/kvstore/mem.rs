@@ -0,0 +1,759 @@
+//! In-memory key/value store which implements all kvstore traits.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::item::{Item, RawItem};
+use crate::{bucket::Bucket, count::Count, datetime::DateTime, evt::Event};
+
+use crate::kvstore::conflict::ConflictUpsert;
+use crate::kvstore::count::{Cache, Counter};
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{DeleteRange, DeleteRow, DropBucket};
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::list::{ListBuckets, ListKeys, ListKeysAfter};
+use crate::kvstore::transaction::Transactional;
+use crate::kvstore::upsert::{BatchUpsert, UpsertRaw};
+
+/// Simple in-memory store which can be used for tests.
+///
+/// Buckets must be created before upsert(like tables in RDB).
+///
+/// Transactions are supported using a snapshot(see `Transactional`).
+///
+/// The snapshot is not a part of the store(ignored by `Clone`, `Debug` and `PartialEq`).
+#[derive(Default)]
+pub struct MemStore {
+    buckets: BTreeMap<Bucket, BTreeMap<Vec<u8>, Vec<u8>>>,
+    counts: BTreeMap<Bucket, Count>,
+    snapshot: Option<Box<MemStore>>,
+}
+
+impl Clone for MemStore {
+    /// Clones buckets and cached counts(without the snapshot).
+    fn clone(&self) -> Self {
+        Self {
+            buckets: self.buckets.clone(),
+            counts: self.counts.clone(),
+            snapshot: None,
+        }
+    }
+}
+
+impl fmt::Debug for MemStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemStore")
+            .field("buckets", &self.buckets)
+            .field("counts", &self.counts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for MemStore {
+    fn eq(&self, other: &Self) -> bool {
+        self.buckets.eq(&other.buckets) && self.counts.eq(&other.counts)
+    }
+}
+
+impl Eq for MemStore {}
+
+impl MemStore {
+    /// Creates new empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn bucket_mut(&mut self, b: &Bucket) -> Result<&mut BTreeMap<Vec<u8>, Vec<u8>>, Event> {
+        self.buckets
+            .get_mut(b)
+            .ok_or_else(|| Event::BucketNotFound(b.as_str().into()))
+    }
+
+    fn bucket_ref(&self, b: &Bucket) -> Result<&BTreeMap<Vec<u8>, Vec<u8>>, Event> {
+        self.buckets
+            .get(b)
+            .ok_or_else(|| Event::BucketNotFound(b.as_str().into()))
+    }
+
+    /// Counts number of rows in a bucket.
+    pub fn count(&self, b: &Bucket) -> Result<u64, Event> {
+        let m = self.bucket_ref(b)?;
+        Ok(m.len() as u64)
+    }
+
+    /// Gets all items from a bucket.
+    pub fn items(&self, b: &Bucket) -> Result<Vec<RawItem>, Event> {
+        let m = self.bucket_ref(b)?;
+        Ok(m.iter()
+            .map(|(k, v)| Item::new(k.clone(), v.clone()))
+            .collect())
+    }
+
+    /// Checks if a transaction is in progress.
+    pub fn in_transaction(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// Nop finalizer which can be used as a `finalize` closure.
+    pub fn finalize_nop<T>(_: T) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl Create for MemStore {
+    /// Creates a bucket if not exists(returns 1 if created).
+    fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        match self.buckets.contains_key(b) {
+            true => Ok(0),
+            false => {
+                self.buckets.insert(b.clone(), BTreeMap::new());
+                Ok(1)
+            }
+        }
+    }
+}
+
+impl UpsertRaw for MemStore {
+    /// Upserts an item(returns 0 if the value is not changed).
+    fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        let m = self.bucket_mut(b)?;
+        let key: &[u8] = i.as_key();
+        let val: &[u8] = i.as_val();
+        match m.get(key) {
+            Some(old) if old.as_slice().eq(val) => Ok(0),
+            _ => {
+                m.insert(key.to_vec(), val.to_vec());
+                Ok(1)
+            }
+        }
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl BatchUpsert for MemStore {}
+
+impl ConflictUpsert for MemStore {}
+
+impl DropBucket for MemStore {
+    /// Drops a bucket if exists(returns 1 if dropped).
+    fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        self.counts.remove(b);
+        Ok(self.buckets.remove(b).map(|_| 1).unwrap_or(0))
+    }
+}
+
+impl DeleteRow for MemStore {
+    fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        let m = self.bucket_mut(b)?;
+        Ok(m.remove(key).map(|_| 1).unwrap_or(0))
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl DeleteRange for MemStore {
+    fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        let m = self.bucket_mut(b)?;
+        let keys: Vec<Vec<u8>> = m
+            .keys()
+            .filter(|k| lower.le(k.as_slice()) && upper.gt(k.as_slice()))
+            .cloned()
+            .collect();
+        keys.iter().for_each(|k| {
+            m.remove(k);
+        });
+        Ok(keys.len() as u64)
+    }
+}
+
+impl ListBuckets for MemStore {
+    fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        Ok(self.buckets.keys().cloned().collect())
+    }
+}
+
+impl ListKeys<Vec<u8>> for MemStore {
+    fn list(&mut self, b: &Bucket) -> Result<Vec<Vec<u8>>, Event> {
+        let m = self.bucket_ref(b)?;
+        Ok(m.keys().cloned().collect())
+    }
+}
+
+impl ListKeysAfter for MemStore {
+    fn list_after(
+        &mut self,
+        b: &Bucket,
+        after: Option<&[u8]>,
+        limit: u32,
+    ) -> Result<Vec<Vec<u8>>, Event> {
+        let m = self.bucket_ref(b)?;
+        Ok(m.keys()
+            .filter(|k| after.map(|a| a.lt(k.as_slice())).unwrap_or(true))
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+}
+
+impl GetRaw for MemStore {
+    fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        let m = self.bucket_ref(b)?;
+        Ok(m.get(key).cloned())
+    }
+
+    fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        Ok(self.buckets.contains_key(b))
+    }
+}
+
+impl Cache for MemStore {
+    fn read(&mut self, b: &Bucket) -> Result<Count, Event> {
+        self.counts
+            .get(b)
+            .copied()
+            .ok_or_else(|| Event::NotCached(b.as_str().into()))
+    }
+
+    fn write(&mut self, b: &Bucket, c: &Count) -> Result<(), Event> {
+        self.counts.insert(b.clone(), *c);
+        Ok(())
+    }
+}
+
+impl Counter for MemStore {
+    /// Counts number of rows using the current date/time.
+    fn count(&mut self, b: &Bucket) -> Result<Count, Event> {
+        let cnt: u64 = MemStore::count(self, b)?;
+        let dt: DateTime = DateTime::time_source_new_std()()?;
+        Ok(Count::new(cnt, dt))
+    }
+}
+
+impl Transactional for MemStore {
+    /// Saves a snapshot(nested transactions are not supported).
+    fn begin(&mut self) -> Result<(), Event> {
+        match self.snapshot {
+            Some(_) => Err(Event::UnexpectedError(String::from(
+                "Transaction already started",
+            ))),
+            None => {
+                self.snapshot = Some(Box::new(self.clone()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Discards the snapshot.
+    fn commit(&mut self) -> Result<(), Event> {
+        self.snapshot
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| Event::UnexpectedError(String::from("No transaction")))
+    }
+
+    /// Restores the snapshot.
+    fn rollback(&mut self) -> Result<(), Event> {
+        let snapshot: Box<MemStore> = self
+            .snapshot
+            .take()
+            .ok_or_else(|| Event::UnexpectedError(String::from("No transaction")))?;
+        *self = *snapshot;
+        Ok(())
+    }
+}
+
+impl Create for &mut MemStore {
+    fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        Create::create(*self, b)
+    }
+}
+
+impl UpsertRaw for &mut MemStore {
+    fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        UpsertRaw::upsert(*self, b, i)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl BatchUpsert for &mut MemStore {}
+
+impl ConflictUpsert for &mut MemStore {}
+
+impl DropBucket for &mut MemStore {
+    fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        DropBucket::drop(*self, b)
+    }
+}
+
+impl DeleteRow for &mut MemStore {
+    fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        DeleteRow::delete(*self, b, key)
+    }
+
+    /// Nop(the store itself is not consumed).
+    fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl DeleteRange for &mut MemStore {
+    fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        DeleteRange::delete_range(*self, b, lower, upper)
+    }
+}
+
+impl ListBuckets for &mut MemStore {
+    fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        ListBuckets::list(*self)
+    }
+}
+
+impl ListKeys<Vec<u8>> for &mut MemStore {
+    fn list(&mut self, b: &Bucket) -> Result<Vec<Vec<u8>>, Event> {
+        ListKeys::list(*self, b)
+    }
+}
+
+impl ListKeysAfter for &mut MemStore {
+    fn list_after(
+        &mut self,
+        b: &Bucket,
+        after: Option<&[u8]>,
+        limit: u32,
+    ) -> Result<Vec<Vec<u8>>, Event> {
+        ListKeysAfter::list_after(*self, b, after, limit)
+    }
+}
+
+impl GetRaw for &mut MemStore {
+    fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        GetRaw::get(*self, b, key)
+    }
+
+    fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        GetRaw::chk(*self, b)
+    }
+}
+
+/// Creates a store which has data buckets for 2022_12_31 and 2023_01_02 and
+/// the range 2022_12_31..=2023_01_02(tests only).
+#[cfg(test)]
+pub(crate) fn range_fixture(
+    dev: &crate::device::Device,
+) -> (MemStore, crate::daterange::DateRange) {
+    use crate::{data::Data, date::Date, daterange::DateRange, kvstore::upsert};
+    let mut m: MemStore = MemStore::new();
+    let source = [
+        ("2022_12_31", b"42".to_vec()),
+        ("2023_01_02", b"634".to_vec()),
+    ]
+    .into_iter()
+    .map(|(d, v): (&str, Vec<u8>)| {
+        Data::new(
+            dev.clone(),
+            Date::new_unchecked(d.into()),
+            Item::new(b"23:59:59.0Z".to_vec(), v),
+        )
+    });
+    let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+    upsert::upsert_all(source, &mut cu).unwrap();
+    let lbi: Date = Date::parse("2022_12_31").unwrap();
+    let ubi: Date = Date::parse("2023_01_02").unwrap();
+    (m, DateRange::inclusive(&lbi, &ubi).unwrap())
+}
+
+#[cfg(test)]
+mod test_mem {
+
+    mod upsert_all_shared {
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::create::Create;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert::{self, UpsertRaw};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device, evt::Event};
+
+        #[test]
+        fn test_upsert() {
+            let mut m: MemStore = MemStore::new();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let cnt: u64 = upsert::upsert_all_shared(
+                |s: &mut &mut MemStore, b: &Bucket, i: &RawItem| s.upsert(b, i),
+                |s: &mut &mut MemStore, b: &Bucket| s.create(b),
+                &mut m,
+                MemStore::finalize_nop,
+                source.into_iter(),
+            )
+            .unwrap();
+
+            // 7 buckets created, 2 data rows, 1 + 1 + 2 + 1 + 2 master rows.
+            assert_eq!(cnt, 16);
+
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ));
+            assert_eq!(m.count(&b).unwrap(), 1);
+            assert_eq!(m.count(&Bucket::new_devices_master()).unwrap(), 2);
+            assert_eq!(m.count(&Bucket::new_dates_master()).unwrap(), 1);
+        }
+
+        #[test]
+        fn test_missing_bucket() {
+            let mut m: MemStore = MemStore::new();
+            let b: Bucket = Bucket::new_dates_master();
+            let i: RawItem = Item::new(b"2022_11_02".to_vec(), vec![]);
+            let r: Result<_, _> = m.upsert(&b, &i);
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+        }
+    }
+
+    mod delete_device_default {
+        use crate::item::Item;
+        use crate::kvstore::delete;
+        use crate::kvstore::list::ListBuckets;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_delete() {
+            let mut m: MemStore = MemStore::new();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source.into_iter(), &mut cu).unwrap();
+
+            let cnt: u64 = delete::delete_device_default(
+                &mut m,
+                Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+            )
+            .unwrap();
+
+            // 2 buckets dropped, 2 rows deleted(devices, devices_2022_11_02).
+            assert_eq!(cnt, 4);
+
+            let buckets: Vec<Bucket> = ListBuckets::list(&mut m).unwrap();
+            assert_eq!(buckets.len(), 5);
+            assert_eq!(m.count(&Bucket::new_devices_master()).unwrap(), 1);
+        }
+    }
+
+    mod list_keys_after {
+        use crate::bucket::Bucket;
+        use crate::item::Item;
+        use crate::kvstore::create::Create;
+        use crate::kvstore::list::ListKeysAfter;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert::UpsertRaw;
+
+        #[test]
+        fn test_pages() {
+            let mut m: MemStore = MemStore::new();
+            let b: Bucket = Bucket::new_dates_master();
+            Create::create(&mut m, &b).unwrap();
+            for d in ["2022_11_01", "2022_11_02", "2022_11_03"] {
+                UpsertRaw::upsert(&mut m, &b, &Item::new(d.as_bytes().to_vec(), vec![])).unwrap();
+            }
+            let first: Vec<Vec<u8>> = m.list_after(&b, None, 2).unwrap();
+            assert_eq!(first, vec![b"2022_11_01".to_vec(), b"2022_11_02".to_vec()]);
+            let last: Vec<Vec<u8>> = m.list_after(&b, Some(b"2022_11_02"), 2).unwrap();
+            assert_eq!(last, vec![b"2022_11_03".to_vec()]);
+        }
+    }
+
+    mod with_namer {
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::{delete, get, get::GetRaw, list::ListBuckets, upsert};
+        use crate::namer::{BucketNamer, SimpleBucketNamer};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_upsert_get_delete() {
+            let mut m: MemStore = MemStore::new();
+            let namer = SimpleBucketNamer::default()
+                .with_prefix("telemetry_".into())
+                .unwrap()
+                .with_device_first(true);
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date = Date::new_unchecked("2022_11_02".into());
+            let source = vec![Data::new(
+                dev.clone(),
+                date.clone(),
+                Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+            )];
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all_with_namer(
+                source.into_iter(),
+                &mut cu,
+                upsert::upsert_value_generator_new_func_default(),
+                &namer,
+            )
+            .unwrap();
+
+            let mut g = |b: &Bucket, k: &[u8]| GetRaw::get(&mut m, b, k);
+            let found: Option<RawItem> =
+                get::get_raw_with_namer(&mut g, &dev, &date, b"00:30:21.0Z", &namer).unwrap();
+            assert_eq!(found.map(|i| i.as_val().to_vec()), Some(b"42".to_vec()));
+            assert_eq!(m.count(&namer.devices()).unwrap(), 1);
+
+            let cnt: u64 = delete::delete_device_with_namer(&mut m, dev, &namer).unwrap();
+
+            // 2 buckets dropped, 2 rows deleted(devices, devices4date).
+            assert_eq!(cnt, 4);
+            let buckets: Vec<Bucket> = ListBuckets::list(&mut m).unwrap();
+            assert_eq!(buckets.len(), 3);
+        }
+    }
+
+    mod delete_stale_data_range_default {
+        use crate::item::Item;
+        use crate::kvstore::delete::{self, DeleteRange};
+        use crate::kvstore::list::ListBuckets;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let source = ["2022_11_01", "2022_11_02", "2022_11_03", "2022_11_04"]
+                .into_iter()
+                .map(|d: &str| {
+                    Data::new(
+                        dev.clone(),
+                        Date::new_unchecked(d.into()),
+                        Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                    )
+                });
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source, &mut cu).unwrap();
+            m
+        }
+
+        #[test]
+        fn test_delete() {
+            let mut m: MemStore = store();
+            let cnt: u64 = delete::delete_stale_data_range_default(
+                &mut m,
+                Date::new_unchecked("2022_11_03".into()),
+            )
+            .unwrap();
+
+            // 4 buckets dropped(2 data, 2 devices4date), 2 + 2 rows deleted(dates, dates4device).
+            assert_eq!(cnt, 8);
+
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let dates: Vec<Vec<u8>> = m
+                .items(&Bucket::new_dates_master())
+                .unwrap()
+                .into_iter()
+                .map(|i| i.as_key().to_vec())
+                .collect();
+            assert_eq!(dates, vec![b"2022_11_03".to_vec(), b"2022_11_04".to_vec()]);
+            assert_eq!(
+                m.count(&Bucket::new_dates_master_for_device(&dev)).unwrap(),
+                2
+            );
+            let buckets: Vec<Bucket> = ListBuckets::list(&mut m).unwrap();
+            assert_eq!(buckets.len(), 7);
+        }
+
+        #[test]
+        fn test_bounds() {
+            let mut m: MemStore = store();
+            let b: Bucket = Bucket::new_dates_master();
+            let cnt: u64 = m.delete_range(&b, b"2022_11_02", b"2022_11_04").unwrap();
+            assert_eq!(cnt, 2);
+            assert_eq!(m.count(&b).unwrap(), 2);
+            let cnt: u64 = m.delete_range(&b, b"2022_11_05", b"2022_11_01").unwrap();
+            assert_eq!(cnt, 0);
+        }
+    }
+
+    mod delete_device_default_report {
+        use crate::item::Item;
+        use crate::kvstore::delete::{self, DeleteRow, DropBucket, RemovalReport};
+        use crate::kvstore::list::ListBuckets;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device, evt::Event};
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source.into_iter(), &mut cu).unwrap();
+            m
+        }
+
+        struct FailOnDelete {
+            m: MemStore,
+            fail: Bucket,
+            fail_finalize: bool,
+        }
+
+        impl DropBucket for FailOnDelete {
+            fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+                DropBucket::drop(&mut self.m, b)
+            }
+        }
+
+        impl DeleteRow for FailOnDelete {
+            fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+                match self.fail.eq(b) {
+                    true => Err(Event::Timeout(b.as_str().into())),
+                    false => DeleteRow::delete(&mut self.m, b, key),
+                }
+            }
+
+            fn finalize(self) -> Result<(), Event> {
+                match self.fail_finalize {
+                    true => Err(Event::Timeout("finalize".into())),
+                    false => Ok(()),
+                }
+            }
+        }
+
+        impl ListBuckets for FailOnDelete {
+            fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+                ListBuckets::list(&mut self.m)
+            }
+        }
+
+        #[test]
+        fn test_report() {
+            let mut m: MemStore = store();
+            let r: RemovalReport = delete::delete_device_default_report(
+                &mut m,
+                Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+            );
+            assert!(r.is_ok());
+            let dropped: Vec<&str> = r.dropped().iter().map(|b| b.as_str()).collect();
+            assert_eq!(
+                dropped,
+                vec![
+                    "data_2022_11_02_cafef00ddeadbeafface864299792458",
+                    "dates_cafef00ddeadbeafface864299792458",
+                ]
+            );
+            assert_eq!(r.deleted().get(&Bucket::new_devices_master()), Some(&1));
+            assert_eq!(r.deleted().len(), 2);
+            assert_eq!(r.skipped().len(), 3);
+            assert_eq!(r.total(), 4);
+        }
+
+        #[test]
+        fn test_failure() {
+            let f = FailOnDelete {
+                m: store(),
+                fail: Bucket::new_devices_master(),
+                fail_finalize: false,
+            };
+            let r: RemovalReport = delete::delete_device_default_report(
+                f,
+                Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+            );
+            assert!(!r.is_ok());
+            assert!(r.dropped().is_empty());
+            assert!(r.deleted().is_empty());
+            assert_eq!(r.attempted_drops().len(), 2);
+            assert_eq!(r.total(), 0);
+            assert_eq!(r.failed(), Some(&Bucket::new_devices_master()));
+            assert!(matches!(r.error(), Some(Event::Timeout(_))));
+        }
+
+        #[test]
+        fn test_finalize_failure() {
+            let f = FailOnDelete {
+                m: store(),
+                fail: Bucket::new_dates_master(),
+                fail_finalize: true,
+            };
+            let r: RemovalReport = delete::delete_device_default_report(
+                f,
+                Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+            );
+            assert!(!r.is_ok());
+            assert!(r.dropped().is_empty());
+            assert!(r.deleted().is_empty());
+            assert_eq!(r.attempted_drops().len(), 2);
+            assert_eq!(r.attempted_deletes().len(), 2);
+            assert_eq!(r.skipped().len(), 3);
+            assert!(r.failed().is_none());
+            assert!(matches!(r.error(), Some(Event::Timeout(_))));
+        }
+    }
+
+    mod get_raw_ignore_missing_bucket_new_func {
+        use crate::item::Item;
+        use crate::kvstore::get;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_get() {
+            let mut m: MemStore = MemStore::new();
+            let source = vec![Data::new(
+                Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                Date::new_unchecked("2022_11_02".into()),
+                Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+            )];
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source.into_iter(), &mut cu).unwrap();
+
+            let mut f = get::get_raw_ignore_missing_bucket_new_func(m);
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ));
+            let found: Option<Vec<u8>> = f(&b, b"00:30:21.0Z").unwrap();
+            assert_eq!(found, Some(b"42".to_vec()));
+
+            let missing: Bucket = Bucket::from(String::from(
+                "data_2022_11_03_cafef00ddeadbeafface864299792458",
+            ));
+            let notfound: Option<Vec<u8>> = f(&missing, b"00:30:21.0Z").unwrap();
+            assert_eq!(notfound, None);
+        }
+    }
+}
@@ -0,0 +1,597 @@
+//! Async variants of the key/value store traits(requires `async` feature).
+//!
+//! Functions take async closures(like the synchronous versions, requires rust 1.85) and
+//! do not depend on any async runtime.
+//!
+//! Futures returned by the trait methods are `Send`(can be used with multi-threaded runtimes).
+
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use crate::item::{Item, RawItem};
+use crate::namer::SimpleBucketNamer;
+use crate::remove::{is_delete_target, is_drop_target_stale};
+use crate::{
+    bucket::Bucket, count::Count, data::RawData, date::Date, datetime::DateTime, device::Device,
+    evt::Event,
+};
+
+use crate::kvstore::count::{Cache, Counter};
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{DeleteRange, DeleteRow, DropBucket};
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::list::ListBuckets;
+use crate::kvstore::mem::MemStore;
+use crate::kvstore::upsert::{self, UpsertRaw, UpsertValueGenerator};
+
+/// Upserts an item into a bucket and finalizes after upserts(async).
+pub trait AsyncUpsertRaw {
+    /// Upserts an item into a bucket.
+    fn upsert(
+        &mut self,
+        b: &Bucket,
+        i: &RawItem,
+    ) -> impl Future<Output = Result<u64, Event>> + Send;
+
+    /// (Optional) Finalize.
+    fn finalize(self) -> impl Future<Output = Result<(), Event>> + Send;
+}
+
+/// Creates a bucket(async).
+pub trait AsyncCreate {
+    fn create(&mut self, b: &Bucket) -> impl Future<Output = Result<u64, Event>> + Send;
+}
+
+/// Gets a value from a bucket(async).
+pub trait AsyncGetRaw {
+    /// Gets a value.
+    fn get(
+        &mut self,
+        b: &Bucket,
+        key: &[u8],
+    ) -> impl Future<Output = Result<Option<Vec<u8>>, Event>> + Send;
+
+    /// Checks if the bucket exists.
+    fn chk(&mut self, b: &Bucket) -> impl Future<Output = Result<bool, Event>> + Send;
+}
+
+/// Drops a bucket(async).
+pub trait AsyncDropBucket {
+    fn drop(&mut self, b: &Bucket) -> impl Future<Output = Result<u64, Event>> + Send;
+}
+
+/// Deletes a row from a bucket and finalizes after deletions(async).
+pub trait AsyncDeleteRow {
+    /// Deletes a row.
+    fn delete(&mut self, b: &Bucket, key: &[u8])
+        -> impl Future<Output = Result<u64, Event>> + Send;
+
+    /// (Optional) Finalize.
+    fn finalize(self) -> impl Future<Output = Result<(), Event>> + Send;
+}
+
+/// Deletes rows in a key range from the bucket(async).
+pub trait AsyncDeleteRange {
+    /// Deletes rows whose keys are in `lower..upper`(bytewise order).
+    ///
+    /// # Arguments
+    /// - b: Target bucket.
+    /// - lower: Lower bound(inclusive). Empty lower bound means the first key.
+    /// - upper: Upper bound(exclusive).
+    fn delete_range(
+        &mut self,
+        b: &Bucket,
+        lower: &[u8],
+        upper: &[u8],
+    ) -> impl Future<Output = Result<u64, Event>> + Send;
+}
+
+/// Gets list of buckets(async).
+pub trait AsyncListBuckets {
+    fn list(&mut self) -> impl Future<Output = Result<Vec<Bucket>, Event>> + Send;
+}
+
+/// Reads/writes cached counts(async).
+pub trait AsyncCache {
+    fn read(&mut self, b: &Bucket) -> impl Future<Output = Result<Count, Event>> + Send;
+    fn write(&mut self, b: &Bucket, c: &Count) -> impl Future<Output = Result<(), Event>> + Send;
+}
+
+/// Counts number of rows in a bucket(async).
+pub trait AsyncCounter {
+    fn count(&mut self, b: &Bucket) -> impl Future<Output = Result<Count, Event>> + Send;
+}
+
+/// Upserts data which creates a bucket before upsert(async).
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - upsert: Upserts an item into a bucket using shared resource.
+/// - create: Creates a bucket using shared resource.
+/// - shared: Vendor specific shared resource for upsert/create.
+/// - finalize: Finalizes the shared resource.
+/// - requests: Data to be upserted.
+/// - upsert_value_gen: Value generator for master buckets.
+pub async fn upsert_all_shared_ex<U, C, T, F, I, G>(
+    upsert: U,
+    create: C,
+    mut shared: T,
+    finalize: F,
+    requests: I,
+    upsert_value_gen: G,
+) -> Result<u64, Event>
+where
+    U: AsyncFn(&mut T, &Bucket, &RawItem) -> Result<u64, Event>,
+    C: AsyncFn(&mut T, &Bucket) -> Result<u64, Event>,
+    F: AsyncFn(T) -> Result<(), Event>,
+    I: Iterator<Item = RawData>,
+    G: UpsertValueGenerator,
+{
+    let namer = SimpleBucketNamer::default();
+    let m: BTreeMap<Bucket, Vec<RawItem>> = upsert::rawdata2map(requests, upsert_value_gen, &namer);
+    let mut tot: u64 = 0;
+    for (b, v) in m {
+        let uniq: Vec<RawItem> = Item::uniq(v);
+        tot += create(&mut shared, &b).await?;
+        for i in uniq.iter() {
+            tot += upsert(&mut shared, &b, i).await?;
+        }
+    }
+    finalize(shared).await?;
+    Ok(tot)
+}
+
+/// Drops stale buckets and deletes stale rows from buckets(async).
+///
+/// Every date key older than lbi will be deleted(same as `delete::delete_stale_data_range`).
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub async fn delete_stale_data<D, T, R>(
+    mut drop_del_list: D,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: Date,
+) -> Result<u64, Event>
+where
+    D: AsyncDropBucket + AsyncDeleteRange + AsyncDeleteRow + AsyncListBuckets,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    let vb: Vec<Bucket> = drop_del_list.list().await?;
+    let mut drop_cnt: u64 = 0;
+    for b in vb.iter().filter(|b| drop_target(b, &lbi)) {
+        drop_cnt += drop_del_list.drop(b).await?;
+    }
+    let mut del_cnt: u64 = 0;
+    for b in vb.iter().filter(|b| remove_target(b)) {
+        del_cnt += drop_del_list.delete_range(b, &[], lbi.as_bytes()).await?;
+    }
+    drop_del_list.finalize().await?;
+    Ok(drop_cnt + del_cnt)
+}
+
+/// Drops stale buckets and deletes stale rows which uses default checkers(async).
+///
+/// # Arguments
+/// - drop_del_list: Gets list of buckets, Drops a bucket, Deletes rows, Finalizes.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub async fn delete_stale_data_default<D>(drop_del_list: D, lbi: Date) -> Result<u64, Event>
+where
+    D: AsyncDropBucket + AsyncDeleteRange + AsyncDeleteRow + AsyncListBuckets,
+{
+    delete_stale_data(drop_del_list, &is_drop_target_stale, &is_delete_target, lbi).await
+}
+
+/// Counts number of rows in a data bucket(async).
+///
+/// # Arguments
+/// - counter: Counts number of rows in a bucket.
+/// - dev: Target device.
+/// - date: Target date.
+/// - time_source: Gets current date/time.
+pub async fn count_data_bucket4date<C, T>(
+    counter: &mut C,
+    dev: &Device,
+    date: &Date,
+    time_source: &T,
+) -> Result<Count, Event>
+where
+    C: AsyncFnMut(&Bucket) -> Result<u64, Event>,
+    T: Fn() -> Result<DateTime, Event>,
+{
+    let b: Bucket = Bucket::new_data_bucket(dev, date);
+    let cnt: u64 = counter(&b).await?;
+    let dt: DateTime = time_source()?;
+    Ok(Count::new(cnt, dt))
+}
+
+/// Creates new counter which counts number of rows of a data bucket(async).
+///
+/// # Arguments
+/// - counter: Counts number of rows in a bucket.
+/// - time_source: Gets current date/time.
+pub fn count_data_bucket4date_new<C, T>(
+    mut counter: C,
+    time_source: T,
+) -> impl AsyncFnMut(&Device, &Date) -> Result<Count, Event>
+where
+    C: AsyncFnMut(&Bucket) -> Result<u64, Event>,
+    T: Fn() -> Result<DateTime, Event>,
+{
+    async move |dev: &Device, d: &Date| {
+        count_data_bucket4date(&mut counter, dev, d, &time_source).await
+    }
+}
+
+/// Simple async in-memory store which can be used for tests.
+///
+/// Buckets must be created before upsert(like `MemStore`).
+#[derive(Debug, Default, Clone)]
+pub struct AsyncMemStore {
+    inner: MemStore,
+}
+
+impl AsyncMemStore {
+    /// Creates new empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the underlying store.
+    pub fn as_inner(&self) -> &MemStore {
+        &self.inner
+    }
+
+    /// Converts into the underlying store.
+    pub fn into_inner(self) -> MemStore {
+        self.inner
+    }
+}
+
+impl From<MemStore> for AsyncMemStore {
+    fn from(inner: MemStore) -> Self {
+        Self { inner }
+    }
+}
+
+impl AsyncCreate for AsyncMemStore {
+    async fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        Create::create(&mut self.inner, b)
+    }
+}
+
+impl AsyncUpsertRaw for AsyncMemStore {
+    async fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        UpsertRaw::upsert(&mut self.inner, b, i)
+    }
+
+    async fn finalize(self) -> Result<(), Event> {
+        UpsertRaw::finalize(self.inner)
+    }
+}
+
+impl AsyncGetRaw for AsyncMemStore {
+    async fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        GetRaw::get(&mut self.inner, b, key)
+    }
+
+    async fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        GetRaw::chk(&mut self.inner, b)
+    }
+}
+
+impl AsyncDropBucket for AsyncMemStore {
+    async fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        DropBucket::drop(&mut self.inner, b)
+    }
+}
+
+impl AsyncDeleteRow for AsyncMemStore {
+    async fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        DeleteRow::delete(&mut self.inner, b, key)
+    }
+
+    async fn finalize(self) -> Result<(), Event> {
+        DeleteRow::finalize(self.inner)
+    }
+}
+
+impl AsyncDeleteRange for AsyncMemStore {
+    async fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        DeleteRange::delete_range(&mut self.inner, b, lower, upper)
+    }
+}
+
+impl AsyncListBuckets for AsyncMemStore {
+    async fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        ListBuckets::list(&mut self.inner)
+    }
+}
+
+impl AsyncCache for AsyncMemStore {
+    async fn read(&mut self, b: &Bucket) -> Result<Count, Event> {
+        Cache::read(&mut self.inner, b)
+    }
+
+    async fn write(&mut self, b: &Bucket, c: &Count) -> Result<(), Event> {
+        Cache::write(&mut self.inner, b, c)
+    }
+}
+
+impl AsyncCounter for AsyncMemStore {
+    async fn count(&mut self, b: &Bucket) -> Result<Count, Event> {
+        Counter::count(&mut self.inner, b)
+    }
+}
+
+impl AsyncCreate for &mut AsyncMemStore {
+    async fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        AsyncCreate::create(*self, b).await
+    }
+}
+
+impl AsyncUpsertRaw for &mut AsyncMemStore {
+    async fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        AsyncUpsertRaw::upsert(*self, b, i).await
+    }
+
+    async fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl AsyncDropBucket for &mut AsyncMemStore {
+    async fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        AsyncDropBucket::drop(*self, b).await
+    }
+}
+
+impl AsyncDeleteRow for &mut AsyncMemStore {
+    async fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        AsyncDeleteRow::delete(*self, b, key).await
+    }
+
+    async fn finalize(self) -> Result<(), Event> {
+        Ok(())
+    }
+}
+
+impl AsyncDeleteRange for &mut AsyncMemStore {
+    async fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        AsyncDeleteRange::delete_range(*self, b, lower, upper).await
+    }
+}
+
+impl AsyncListBuckets for &mut AsyncMemStore {
+    async fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        AsyncListBuckets::list(*self).await
+    }
+}
+
+#[cfg(test)]
+mod test_nonblocking {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Runs a future on the current thread(busy loop, tests only).
+    pub(crate) fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(o) = f.as_mut().poll(&mut cx) {
+                return o;
+            }
+        }
+    }
+
+    fn source() -> impl Iterator<Item = crate::data::RawData> {
+        use crate::{data::Data, date::Date, device::Device, item::Item};
+        ["2022_11_01", "2022_11_02", "2022_11_02"]
+            .into_iter()
+            .map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            })
+    }
+
+    mod upsert_all_shared_ex {
+        use crate::item::RawItem;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::nonblocking::test_nonblocking::{block_on, source};
+        use crate::kvstore::nonblocking::AsyncUpsertRaw;
+        use crate::kvstore::nonblocking::{self, AsyncCreate, AsyncGetRaw, AsyncMemStore};
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, evt::Event};
+
+        #[test]
+        fn test_same_as_sync() {
+            let mut m: MemStore = MemStore::new();
+            let expected: u64 = upsert::upsert_all_shared_ex(
+                |s: &mut &mut MemStore, b: &Bucket, i: &RawItem| {
+                    crate::kvstore::upsert::UpsertRaw::upsert(*s, b, i)
+                },
+                |s: &mut &mut MemStore, b: &Bucket| crate::kvstore::create::Create::create(*s, b),
+                &mut m,
+                MemStore::finalize_nop,
+                source(),
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+
+            let mut a: AsyncMemStore = AsyncMemStore::new();
+            let got: u64 = block_on(nonblocking::upsert_all_shared_ex(
+                async |s: &mut &mut AsyncMemStore, b: &Bucket, i: &RawItem| s.upsert(b, i).await,
+                async |s: &mut &mut AsyncMemStore, b: &Bucket| s.create(b).await,
+                &mut a,
+                async |_: &mut AsyncMemStore| Ok(()),
+                source(),
+                upsert::upsert_value_generator_new_func_default(),
+            ))
+            .unwrap();
+            assert_eq!(got, expected);
+            assert_eq!(a.as_inner(), &m);
+
+            let b: Bucket = Bucket::new_dates_master();
+            assert!(block_on(a.chk(&b)).unwrap());
+            assert!(block_on(a.get(&b, b"2022_11_02")).unwrap().is_some());
+        }
+
+        #[test]
+        fn test_finalize_error() {
+            let r = block_on(nonblocking::upsert_all_shared_ex(
+                async |_: &mut (), _: &Bucket, _: &RawItem| Ok(1),
+                async |_: &mut (), _: &Bucket| Ok(0),
+                (),
+                async |_: ()| Err(Event::UnexpectedError("commit".into())),
+                source(),
+                upsert::upsert_value_generator_new_func_default(),
+            ));
+            assert!(matches!(r, Err(Event::UnexpectedError(_))));
+        }
+    }
+
+    mod delete_stale_data_default {
+        use crate::kvstore::delete;
+        use crate::kvstore::get::GetRaw;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::nonblocking::test_nonblocking::{block_on, source};
+        use crate::kvstore::nonblocking::{self, AsyncMemStore};
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, date::Date, item::RawItem};
+
+        #[test]
+        fn test_same_as_sync() {
+            let mut m: MemStore = MemStore::new();
+            let mut cu = |b: &Bucket, i: &RawItem| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source(), &mut cu).unwrap();
+            let mut a: AsyncMemStore = AsyncMemStore::from(m.clone());
+
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            let expected: u64 =
+                delete::delete_stale_data_range_default(&mut m, lbi.clone()).unwrap();
+            let got: u64 = block_on(nonblocking::delete_stale_data_default(&mut a, lbi)).unwrap();
+            assert_eq!(got, expected);
+            assert!(0 < got);
+            let mut got: MemStore = a.into_inner();
+            let dates: Bucket = Bucket::new_dates_master();
+            assert!(GetRaw::get(&mut got, &dates, b"2022_11_01")
+                .unwrap()
+                .is_none());
+            assert!(GetRaw::get(&mut got, &dates, b"2022_11_02")
+                .unwrap()
+                .is_some());
+            assert_eq!(got, m);
+        }
+    }
+
+    mod count_data_bucket4date_new {
+        use crate::kvstore::nonblocking::test_nonblocking::block_on;
+        use crate::kvstore::nonblocking::{
+            self, AsyncCounter, AsyncCreate, AsyncMemStore, AsyncUpsertRaw,
+        };
+        use crate::{
+            bucket::Bucket, count::Count, date::Date, datetime::DateTime, device::Device,
+            evt::Event, item::Item,
+        };
+
+        #[test]
+        fn test_count() {
+            let dev: Device = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date: Date = Date::new_unchecked("2022_11_01".into());
+            let b: Bucket = Bucket::new_data_bucket(&dev, &date);
+            let mut a: AsyncMemStore = AsyncMemStore::new();
+            block_on(async {
+                a.create(&b).await?;
+                a.upsert(&b, &Item::new(b"k1".to_vec(), vec![])).await?;
+                a.upsert(&b, &Item::new(b"k2".to_vec(), vec![])).await
+            })
+            .unwrap();
+
+            let dt: DateTime = DateTime::time_source_new_std()().unwrap();
+            let mut counter = nonblocking::count_data_bucket4date_new(
+                async |b: &Bucket| a.count(b).await.map(|c: Count| c.as_count()),
+                move || Ok(dt),
+            );
+            let c: Count = block_on(counter(&dev, &date)).unwrap();
+            assert_eq!(c, Count::new(2, dt));
+
+            let missing: Date = Date::new_unchecked("2022_11_02".into());
+            let r: Result<Count, Event> = block_on(counter(&dev, &missing));
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+        }
+    }
+
+    mod async_cache {
+        use crate::kvstore::nonblocking::test_nonblocking::block_on;
+        use crate::kvstore::nonblocking::{AsyncCache, AsyncCounter, AsyncCreate, AsyncMemStore};
+        use crate::{bucket::Bucket, count::Count, datetime::DateTime, evt::Event};
+
+        #[test]
+        fn test_read_write() {
+            let b: Bucket = Bucket::from(String::from("data"));
+            let mut a: AsyncMemStore = AsyncMemStore::new();
+            let r: Result<Count, Event> = block_on(a.read(&b));
+            assert!(matches!(r, Err(Event::NotCached(_))));
+
+            let c: Count = Count::new(42, DateTime::default());
+            block_on(a.write(&b, &c)).unwrap();
+            assert_eq!(block_on(a.read(&b)).unwrap(), c);
+
+            block_on(a.create(&b)).unwrap();
+            assert_eq!(block_on(a.count(&b)).unwrap().as_count(), 0);
+            assert_eq!(block_on(a.read(&b)).unwrap(), c);
+        }
+    }
+
+    mod send {
+        use std::future::Future;
+
+        use crate::kvstore::nonblocking::test_nonblocking::block_on;
+        use crate::kvstore::nonblocking::{
+            self, AsyncCounter, AsyncCreate, AsyncGetRaw, AsyncMemStore, AsyncUpsertRaw,
+        };
+        use crate::{bucket::Bucket, date::Date, item::RawItem};
+
+        fn assert_send<F>(f: F) -> F
+        where
+            F: Future + Send,
+        {
+            f
+        }
+
+        /// Generic callers can move the futures to other threads(e.g, `tokio::spawn`).
+        async fn upsert_get_count<S>(s: &mut S, b: &Bucket, i: &RawItem) -> u64
+        where
+            S: AsyncUpsertRaw + AsyncGetRaw + AsyncCounter + Send,
+        {
+            let upserted: u64 = assert_send(s.upsert(b, i)).await.unwrap_or(0);
+            let _ = assert_send(s.get(b, i.as_key())).await;
+            let cnt: u64 = assert_send(s.count(b))
+                .await
+                .map(|c| c.as_count())
+                .unwrap_or(0);
+            upserted + cnt
+        }
+
+        #[test]
+        fn test_send() {
+            let mut a: AsyncMemStore = AsyncMemStore::new();
+            let b: Bucket = Bucket::from(String::from("data"));
+            let i: RawItem = RawItem::new(b"k".to_vec(), vec![]);
+            block_on(a.create(&b)).unwrap();
+            assert_eq!(block_on(assert_send(upsert_get_count(&mut a, &b, &i))), 2);
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            let f = assert_send(nonblocking::delete_stale_data_default(&mut a, lbi));
+            assert_eq!(block_on(f).unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Upserts data buckets in parallel.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::item::{Item, RawItem};
+use crate::namer::SimpleBucketNamer;
+use crate::{bucket::Bucket, data::RawData, evt::Event};
+
+use crate::kvstore::upsert::{self, DataGroup, UpsertValueGenerator};
+
+/// Results of a parallel upsert.
+#[derive(Debug, Default)]
+pub struct ParallelOutcome {
+    upserted: u64,
+    errors: Vec<(Bucket, Event)>,
+}
+
+impl ParallelOutcome {
+    /// Gets the total of upsert results of finalized buckets.
+    pub fn upserted(&self) -> u64 {
+        self.upserted
+    }
+
+    /// Gets failed buckets with errors(sorted by bucket).
+    pub fn errors(&self) -> &[(Bucket, Event)] {
+        &self.errors
+    }
+
+    /// Checks if all buckets are upserted.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Gets the total of upsert results or the first error.
+    pub fn into_result(self) -> Result<u64, Event> {
+        match self.errors.into_iter().next() {
+            None => Ok(self.upserted),
+            Some((_, e)) => Err(e),
+        }
+    }
+}
+
+/// Master rows which refer to a data bucket.
+type MasterRows = Vec<(Bucket, RawItem)>;
+
+/// Results of a worker: upserted, finalized buckets, errors.
+type WorkerResult = (u64, Vec<Bucket>, Vec<(Bucket, Event)>);
+
+fn upsert_group<T, B, C>(
+    shared: &mut T,
+    upsert_batch: &B,
+    create: &C,
+    b: &Bucket,
+    items: Vec<RawItem>,
+) -> Result<u64, Event>
+where
+    B: Fn(&mut T, &Bucket, &[RawItem]) -> Result<u64, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event>,
+{
+    let uniq: Vec<RawItem> = Item::uniq(items);
+    let cnt_c: u64 = create(shared, b)?;
+    let cnt_u: u64 = upsert_batch(shared, b, &uniq)?;
+    Ok(cnt_c + cnt_u)
+}
+
+/// Upserts buckets until the queue becomes empty, then finalizes.
+fn work<T, B, C, F, Q>(
+    mut shared: T,
+    upsert_batch: &B,
+    create: &C,
+    finalize: &F,
+    mut next: Q,
+) -> WorkerResult
+where
+    B: Fn(&mut T, &Bucket, &[RawItem]) -> Result<u64, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event>,
+    F: Fn(T) -> Result<(), Event>,
+    Q: FnMut() -> Option<(Bucket, Vec<RawItem>)>,
+{
+    let mut upserted: u64 = 0;
+    let mut done: Vec<Bucket> = vec![];
+    let mut errors: Vec<(Bucket, Event)> = vec![];
+    while let Some((b, items)) = next() {
+        match upsert_group(&mut shared, upsert_batch, create, &b, items) {
+            Ok(cnt) => {
+                upserted += cnt;
+                done.push(b);
+            }
+            Err(e) => errors.push((b, e)),
+        }
+    }
+    match finalize(shared) {
+        Ok(_) => (upserted, done, errors),
+        Err(e) => {
+            let e: Arc<Event> = Arc::new(e);
+            let failed = done.into_iter().map(|b: Bucket| {
+                let msg: String = format!("Unable to finalize: {}", e);
+                (b, Event::backend(msg, e.is_retriable(), e.clone()))
+            });
+            errors.extend(failed);
+            (0, vec![], errors)
+        }
+    }
+}
+
+/// Locks the mutex even if a worker panicked while holding it.
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Reports all buckets claimed by a panicked worker as failed(nothing finalized).
+fn panicked<I>(payload: Box<dyn Any + Send>, claimed: I) -> WorkerResult
+where
+    I: Iterator<Item = Bucket>,
+{
+    let reason: &str = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| s.as_str()))
+        .unwrap_or("unknown");
+    let msg: String = format!("Worker panicked: {}", reason);
+    let errors = claimed
+        .map(|b: Bucket| (b, Event::UnexpectedError(msg.clone())))
+        .collect();
+    (0, vec![], errors)
+}
+
+/// Upserts data buckets using workers, then upserts master buckets serially.
+///
+/// - Each worker uses its own shared resource created by the factory(e.g, a connection).
+/// - Data buckets are upserted in parallel and finalized per worker.
+/// - Master rows are upserted by a single resource only for finalized data buckets.
+/// - Failed buckets are reported with errors(other buckets are still upserted).
+/// - All buckets claimed by a panicked worker are reported as failed(the resource is not finalized).
+/// - Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - workers: Number of workers(shared resources) for data buckets.
+/// - factory: Creates a shared resource(called `workers + 1` times before upserts).
+/// - upsert_batch: Upserts items into a bucket using shared resource.
+/// - create: Creates a bucket using shared resource.
+/// - finalize: Finalizes the shared resource.
+/// - upsert_value_gen: Value generator for master buckets.
+///
+/// # Errors
+/// The factory failed(nothing upserted).
+pub fn upsert_all_parallel<I, M, T, B, C, F, G>(
+    source: I,
+    workers: NonZeroUsize,
+    factory: M,
+    upsert_batch: B,
+    create: C,
+    finalize: F,
+    upsert_value_gen: G,
+) -> Result<ParallelOutcome, Event>
+where
+    I: Iterator<Item = RawData>,
+    M: Fn() -> Result<T, Event>,
+    T: Send,
+    B: Fn(&mut T, &Bucket, &[RawItem]) -> Result<u64, Event> + Sync,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event> + Sync,
+    F: Fn(T) -> Result<(), Event> + Sync,
+    G: UpsertValueGenerator,
+{
+    let resources: Vec<T> = (0..workers.get())
+        .map(|_| factory())
+        .collect::<Result<_, _>>()?;
+    let master: T = factory()?;
+
+    let namer = SimpleBucketNamer::default();
+    let groups: BTreeMap<Bucket, DataGroup> =
+        upsert::rawdata2groups(source, &upsert_value_gen, &namer);
+    let (data, mut masters): (Vec<_>, BTreeMap<Bucket, MasterRows>) = groups
+        .into_iter()
+        .map(|(b, (items, m))| ((b.clone(), items), (b, m)))
+        .unzip();
+
+    let queue: Mutex<std::vec::IntoIter<(Bucket, Vec<RawItem>)>> = Mutex::new(data.into_iter());
+    let claims: Vec<Mutex<Vec<Bucket>>> = (0..workers.get()).map(|_| Mutex::default()).collect();
+    let mut results: Vec<WorkerResult> = thread::scope(|s| {
+        let handles: Vec<_> = resources
+            .into_iter()
+            .zip(claims.iter())
+            .map(|(shared, claimed): (T, &Mutex<Vec<Bucket>>)| {
+                let queue = &queue;
+                let next = move || {
+                    let got = queue.lock().ok().and_then(|mut q| q.next());
+                    if let Some((b, _)) = &got {
+                        lock(claimed).push(b.clone());
+                    }
+                    got
+                };
+                let (upsert_batch, create, finalize) = (&upsert_batch, &create, &finalize);
+                s.spawn(move || work(shared, upsert_batch, create, finalize, next))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .zip(claims.iter())
+            .map(|(h, claimed)| {
+                h.join()
+                    .unwrap_or_else(|p| panicked(p, lock(claimed).drain(..)))
+            })
+            .collect()
+    });
+    let left: Vec<Bucket> = lock(&queue).by_ref().map(|(b, _)| b).collect();
+    if !left.is_empty() {
+        let msg: &str = "Not upserted(all workers stopped)";
+        let errors = left
+            .into_iter()
+            .map(|b: Bucket| (b, Event::UnexpectedError(msg.into())))
+            .collect();
+        results.push((0, vec![], errors));
+    }
+
+    let mut outcome: ParallelOutcome = ParallelOutcome::default();
+    let mut master_rows: BTreeMap<Bucket, Vec<RawItem>> = BTreeMap::new();
+    for (upserted, done, errors) in results {
+        outcome.upserted += upserted;
+        outcome.errors.extend(errors);
+        let rows = done
+            .iter()
+            .flat_map(|b: &Bucket| masters.remove(b).unwrap_or_default());
+        for (b, i) in rows {
+            master_rows.entry(b).or_default().push(i);
+        }
+    }
+
+    let mut next_master = master_rows.into_iter();
+    let (upserted, _, errors) = work(master, &upsert_batch, &create, &finalize, || {
+        next_master.next()
+    });
+    outcome.upserted += upserted;
+    outcome.errors.extend(errors);
+    outcome.errors.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod test_parallel {
+
+    mod upsert_all_parallel {
+        use std::num::NonZeroUsize;
+        use std::sync::{Arc, Mutex};
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::fsck;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::parallel::{self, ParallelOutcome};
+        use crate::kvstore::upsert::{self, BatchUpsert};
+        use crate::{
+            bucket::Bucket,
+            data::{Data, RawData},
+            date::Date,
+            device::Device,
+            evt::Event,
+        };
+
+        type Shared = Arc<Mutex<MemStore>>;
+
+        fn source() -> impl Iterator<Item = RawData> {
+            let devices = [
+                "cafef00ddeadbeafface864299792458",
+                "dafef00ddeadbeafface864299792458",
+            ];
+            let dates = ["2022_11_01", "2022_11_02", "2022_11_03"];
+            devices.into_iter().flat_map(move |dev: &str| {
+                dates.into_iter().map(move |d: &str| {
+                    Data::new(
+                        Device::new_unchecked(dev.into()),
+                        Date::new_unchecked(d.into()),
+                        Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                    )
+                })
+            })
+        }
+
+        fn create(s: &mut Shared, b: &Bucket) -> Result<u64, Event> {
+            let mut m = s.lock().unwrap();
+            crate::kvstore::create::Create::create(&mut *m, b)
+        }
+
+        #[test]
+        fn test_same_as_serial() {
+            let mut serial: MemStore = MemStore::new();
+            let mut cu = |b: &Bucket, i: &RawItem| upsert::create_upsert(&mut serial, b, i);
+            let expected: u64 = upsert::upsert_all(source(), &mut cu).unwrap();
+
+            let shared: Shared = Arc::new(Mutex::new(MemStore::new()));
+            let o: ParallelOutcome = parallel::upsert_all_parallel(
+                source(),
+                NonZeroUsize::new(3).unwrap(),
+                || Ok(shared.clone()),
+                |s: &mut Shared, b: &Bucket, items: &[RawItem]| {
+                    s.lock().unwrap().upsert_batch(b, items)
+                },
+                create,
+                |_: Shared| Ok(()),
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+            assert!(o.is_ok());
+            assert_eq!(o.upserted(), expected);
+            let m: MemStore = shared.lock().unwrap().clone();
+            assert_eq!(m, serial);
+        }
+
+        #[test]
+        fn test_bucket_error() {
+            let shared: Shared = Arc::new(Mutex::new(MemStore::new()));
+            let bad: &str = "data_2022_11_02_dafef00ddeadbeafface864299792458";
+            let o: ParallelOutcome = parallel::upsert_all_parallel(
+                source(),
+                NonZeroUsize::new(2).unwrap(),
+                || Ok(shared.clone()),
+                |s: &mut Shared, b: &Bucket, items: &[RawItem]| match b.as_str().eq(bad) {
+                    true => Err(Event::Timeout(b.as_str().into())),
+                    false => s.lock().unwrap().upsert_batch(b, items),
+                },
+                create,
+                |_: Shared| Ok(()),
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+            assert_eq!(o.errors().len(), 1);
+            assert_eq!(o.errors()[0].0.as_str(), bad);
+            assert!(o.errors()[0].1.is_retriable());
+
+            // no master row refers to the failed(created but empty) bucket
+            let mut m: MemStore = shared.lock().unwrap().clone();
+            let r = fsck::fsck(&mut m).unwrap();
+            assert!(r.dangling().is_empty());
+            assert_eq!(r.orphans(), &[Bucket::from(String::from(bad))]);
+            assert!(o.into_result().is_err());
+        }
+
+        #[test]
+        fn test_worker_panic() {
+            let shared: Shared = Arc::new(Mutex::new(MemStore::new()));
+            let bad: &str = "data_2022_11_02_dafef00ddeadbeafface864299792458";
+            let o: ParallelOutcome = parallel::upsert_all_parallel(
+                source(),
+                NonZeroUsize::new(1).unwrap(),
+                || Ok(shared.clone()),
+                |s: &mut Shared, b: &Bucket, items: &[RawItem]| match b.as_str().eq(bad) {
+                    true => panic!("bad bucket"),
+                    false => s.lock().unwrap().upsert_batch(b, items),
+                },
+                create,
+                |_: Shared| Ok(()),
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+
+            // claimed(not finalized) and unclaimed data buckets
+            assert_eq!(o.upserted(), 0);
+            assert_eq!(o.errors().len(), 6);
+            assert!(o
+                .errors()
+                .iter()
+                .all(|(b, _)| b.as_str().starts_with("data_")));
+            assert!(o.errors().iter().any(|(b, _)| b.as_str().eq(bad)));
+            let panicked: usize = o
+                .errors()
+                .iter()
+                .filter(|(_, e)| e.to_string().contains("bad bucket"))
+                .count();
+            assert_eq!(panicked, 4);
+
+            let mut m: MemStore = shared.lock().unwrap().clone();
+            assert!(fsck::fsck(&mut m).unwrap().dangling().is_empty());
+        }
+
+        #[test]
+        fn test_factory_error() {
+            let r = parallel::upsert_all_parallel(
+                source(),
+                NonZeroUsize::new(2).unwrap(),
+                || Err::<Shared, _>(Event::ConnectError("refused".into())),
+                |_: &mut Shared, _: &Bucket, _: &[RawItem]| Ok(0),
+                |_: &mut Shared, _: &Bucket| Ok(0),
+                |_: Shared| Ok(()),
+                upsert::upsert_value_generator_new_func_default(),
+            );
+            assert!(matches!(r, Err(Event::ConnectError(_))));
+        }
+    }
+}
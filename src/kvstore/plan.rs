@@ -0,0 +1,338 @@
+//! Plans destructive operations which can be reviewed before execution(dry-run).
+
+use crate::remove::{
+    is_delete_target, is_delete_target_device, is_drop_target_device, is_drop_target_stale,
+};
+use crate::{bucket::Bucket, date::Date, device::Device, evt::Event};
+
+use crate::kvstore::delete::{DeleteRow, DropBucket};
+use crate::kvstore::list::{ListBuckets, ListKeys};
+
+/// An action which removes a bucket or a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovalAction {
+    /// Drops the bucket.
+    Drop(Bucket),
+
+    /// Deletes the row(key) from the bucket.
+    Delete(Bucket, Vec<u8>),
+}
+
+/// List of removal actions(drops first, then deletes).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemovalPlan {
+    actions: Vec<RemovalAction>,
+}
+
+impl RemovalPlan {
+    /// Creates new plan from actions.
+    pub fn new(actions: Vec<RemovalAction>) -> Self {
+        Self { actions }
+    }
+
+    /// Gets the planned actions.
+    pub fn actions(&self) -> &[RemovalAction] {
+        &self.actions
+    }
+
+    /// Gets buckets to be dropped.
+    pub fn drops(&self) -> impl Iterator<Item = &Bucket> {
+        self.actions.iter().filter_map(|a: &RemovalAction| match a {
+            RemovalAction::Drop(b) => Some(b),
+            RemovalAction::Delete(_, _) => None,
+        })
+    }
+
+    /// Gets rows to be deleted.
+    pub fn deletes(&self) -> impl Iterator<Item = (&Bucket, &[u8])> {
+        self.actions.iter().filter_map(|a: &RemovalAction| match a {
+            RemovalAction::Drop(_) => None,
+            RemovalAction::Delete(b, k) => Some((b, k.as_slice())),
+        })
+    }
+
+    /// Checks if nothing will be removed.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+impl IntoIterator for RemovalPlan {
+    type Item = RemovalAction;
+    type IntoIter = std::vec::IntoIter<RemovalAction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.actions.into_iter()
+    }
+}
+
+/// Plans removal of a device.
+///
+/// # Arguments
+/// - lister: Gets list of buckets.
+/// - drop_target: Checks if the bucket is drop target.
+/// - remove_target: Checks if the bucket can have delete target row.
+/// - target: The device to be removed.
+pub fn plan_delete_device<L, T, R>(
+    lister: &mut L,
+    drop_target: &T,
+    remove_target: &R,
+    target: &Device,
+) -> Result<RemovalPlan, Event>
+where
+    L: ListBuckets,
+    T: Fn(&Bucket, &Device) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    let vb: Vec<Bucket> = lister.list()?;
+    let drops = vb
+        .iter()
+        .filter(|b| drop_target(b, target))
+        .map(|b| RemovalAction::Drop(b.clone()));
+    let deletes = vb
+        .iter()
+        .filter(|b| remove_target(b))
+        .map(|b| RemovalAction::Delete(b.clone(), target.as_bytes().to_vec()));
+    Ok(RemovalPlan::new(drops.chain(deletes).collect()))
+}
+
+/// Plans removal of a device which uses default checkers.
+///
+/// # Arguments
+/// - lister: Gets list of buckets.
+/// - target: The device to be removed.
+pub fn plan_delete_device_default<L>(lister: &mut L, target: &Device) -> Result<RemovalPlan, Event>
+where
+    L: ListBuckets,
+{
+    plan_delete_device(
+        lister,
+        &is_drop_target_device,
+        &is_delete_target_device,
+        target,
+    )
+}
+
+/// Plans removal of stale data(preview of `delete_stale_data_range`).
+///
+/// Every date key older than lbi in the remove targets will be planned to be deleted.
+///
+/// `remove_stale_data` has no plan counterpart:
+/// its delete closure decides which rows are stale and can not be previewed.
+///
+/// # Arguments
+/// - lister: Gets list of buckets and keys.
+/// - drop_target: Checks if the bucket is stale.
+/// - remove_target: Checks if the bucket can have stale rows.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn plan_delete_stale_data_range<L, T, R>(
+    lister: &mut L,
+    drop_target: &T,
+    remove_target: &R,
+    lbi: &Date,
+) -> Result<RemovalPlan, Event>
+where
+    L: ListBuckets + ListKeys<Vec<u8>>,
+    T: Fn(&Bucket, &Date) -> bool,
+    R: Fn(&Bucket) -> bool,
+{
+    let vb: Vec<Bucket> = ListBuckets::list(lister)?;
+    let drops = vb
+        .iter()
+        .filter(|b| drop_target(b, lbi))
+        .map(|b| RemovalAction::Drop(b.clone()));
+    let mut actions: Vec<RemovalAction> = drops.collect();
+    for b in vb.iter().filter(|b| remove_target(b)) {
+        let keys: Vec<Vec<u8>> = ListKeys::list(lister, b)?;
+        let stale = keys
+            .into_iter()
+            .filter(|k| k.as_slice().lt(lbi.as_bytes()))
+            .map(|k| RemovalAction::Delete(b.clone(), k));
+        actions.extend(stale);
+    }
+    Ok(RemovalPlan::new(actions))
+}
+
+/// Plans removal of stale data which uses default checkers.
+///
+/// Preview of `delete_stale_data_range_default`.
+///
+/// # Arguments
+/// - lister: Gets list of buckets and keys.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn plan_delete_stale_data_range_default<L>(
+    lister: &mut L,
+    lbi: &Date,
+) -> Result<RemovalPlan, Event>
+where
+    L: ListBuckets + ListKeys<Vec<u8>>,
+{
+    plan_delete_stale_data_range(lister, &is_drop_target_stale, &is_delete_target, lbi)
+}
+
+/// Results of an executed plan.
+#[derive(Debug)]
+pub struct PlanOutcome {
+    results: Vec<Result<u64, Event>>,
+    finalized: Option<Result<(), Event>>,
+}
+
+impl PlanOutcome {
+    /// Gets results of executed actions(same order as the plan).
+    ///
+    /// Actions after the first failure are not executed(not included).
+    pub fn results(&self) -> &[Result<u64, Event>] {
+        &self.results
+    }
+
+    /// Gets the finalization result(`None` if not finalized due to a failure).
+    pub fn finalized(&self) -> Option<&Result<(), Event>> {
+        self.finalized.as_ref()
+    }
+
+    /// Checks if all actions succeeded and changes are finalized.
+    pub fn is_ok(&self) -> bool {
+        matches!(self.finalized, Some(Ok(())))
+    }
+
+    /// Gets the total number of removed buckets/rows or the first error.
+    pub fn into_result(self) -> Result<u64, Event> {
+        let tot: u64 = self
+            .results
+            .into_iter()
+            .try_fold(0, |tot, r| r.map(|cnt| cnt + tot))?;
+        match self.finalized {
+            Some(r) => r.map(|_| tot),
+            None => Err(Event::UnexpectedError(String::from("Not finalized"))),
+        }
+    }
+}
+
+/// Executes the plan and finalizes changes if all actions succeeded.
+///
+/// Execution stops at the first failure(changes are not finalized).
+///
+/// # Arguments
+/// - plan: Actions to be executed.
+/// - drop_del: Drops a bucket, Deletes a row, Finalizes.
+pub fn execute_plan<D>(plan: &RemovalPlan, mut drop_del: D) -> PlanOutcome
+where
+    D: DropBucket + DeleteRow,
+{
+    let mut results: Vec<Result<u64, Event>> = Vec::with_capacity(plan.actions().len());
+    for a in plan.actions() {
+        let r: Result<u64, Event> = match a {
+            RemovalAction::Drop(b) => drop_del.drop(b),
+            RemovalAction::Delete(b, k) => drop_del.delete(b, k),
+        };
+        let failed: bool = r.is_err();
+        results.push(r);
+        if failed {
+            return PlanOutcome {
+                results,
+                finalized: None,
+            };
+        }
+    }
+    let finalized: Option<Result<(), Event>> = Some(drop_del.finalize());
+    PlanOutcome { results, finalized }
+}
+
+#[cfg(test)]
+mod test_plan {
+
+    mod plan_delete_stale_data_range_default {
+        use crate::item::Item;
+        use crate::kvstore::delete;
+        use crate::kvstore::list::ListBuckets;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::plan::{self, RemovalAction, RemovalPlan};
+        use crate::kvstore::upsert;
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let source = ["2022_11_01", "2022_11_02", "2022_11_03"]
+                .into_iter()
+                .map(|d: &str| {
+                    Data::new(
+                        dev.clone(),
+                        Date::new_unchecked(d.into()),
+                        Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                    )
+                });
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source, &mut cu).unwrap();
+            m
+        }
+
+        #[test]
+        fn test_dry_run() {
+            let mut m: MemStore = store();
+            let before: Vec<Bucket> = ListBuckets::list(&mut m).unwrap();
+            let lbi: Date = Date::new_unchecked("2022_11_03".into());
+            let p: RemovalPlan = plan::plan_delete_stale_data_range_default(&mut m, &lbi).unwrap();
+            let drops: Vec<&str> = p.drops().map(|b| b.as_str()).collect();
+            assert_eq!(
+                drops,
+                vec![
+                    "data_2022_11_01_cafef00ddeadbeafface864299792458",
+                    "data_2022_11_02_cafef00ddeadbeafface864299792458",
+                    "devices_2022_11_01",
+                    "devices_2022_11_02",
+                ]
+            );
+            assert_eq!(p.deletes().count(), 4);
+            assert!(p.actions().contains(&RemovalAction::Delete(
+                Bucket::new_dates_master(),
+                b"2022_11_01".to_vec()
+            )));
+
+            // nothing removed yet
+            assert_eq!(ListBuckets::list(&mut m).unwrap(), before);
+
+            let cnt: u64 = plan::execute_plan(&p, &mut m).into_result().unwrap();
+            assert_eq!(cnt, 8);
+            assert_eq!(m.count(&Bucket::new_dates_master()).unwrap(), 1);
+            assert!(plan::plan_delete_stale_data_range_default(&mut m, &lbi)
+                .unwrap()
+                .is_empty());
+        }
+
+        #[test]
+        fn test_same_as_delete() {
+            let mut m: MemStore = store();
+            let mut expected: MemStore = m.clone();
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            let p: RemovalPlan = plan::plan_delete_stale_data_range_default(&mut m, &lbi).unwrap();
+            let planned: u64 = plan::execute_plan(&p, &mut m).into_result().unwrap();
+            let deleted: u64 = delete::delete_stale_data_range_default(&mut expected, lbi).unwrap();
+            assert_eq!(planned, deleted);
+            assert_eq!(m, expected);
+        }
+    }
+
+    mod execute_plan {
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::plan::{self, PlanOutcome, RemovalAction, RemovalPlan};
+        use crate::{bucket::Bucket, evt::Event};
+
+        #[test]
+        fn test_stop_at_failure() {
+            let mut m: MemStore = MemStore::new();
+            let p: RemovalPlan = RemovalPlan::new(vec![
+                RemovalAction::Drop(Bucket::new_devices_master()),
+                RemovalAction::Delete(Bucket::new_dates_master(), b"2022_11_01".to_vec()),
+                RemovalAction::Drop(Bucket::new_dates_master()),
+            ]);
+            let o: PlanOutcome = plan::execute_plan(&p, &mut m);
+            assert_eq!(o.results().len(), 2);
+            assert!(matches!(o.results()[0], Ok(0)));
+            assert!(matches!(o.results()[1], Err(Event::BucketNotFound(_))));
+            assert!(o.finalized().is_none());
+            assert!(!o.is_ok());
+            assert!(o.into_result().is_err());
+        }
+    }
+}
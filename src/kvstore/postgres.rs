@@ -0,0 +1,878 @@
+//! PostgreSQL backend(requires `postgres` feature).
+//!
+//! Each bucket is a table in the `public` schema which has a BYTEA key(primary key) and a
+//! BYTEA value.
+//! Bucket names which are not safe identifiers will be rejected(`Event::UnsafeIdentifier`).
+//!
+//! The functions can be used as closures for functions like
+//! [`upsert_all_shared`](crate::kvstore::upsert::upsert_all_shared)
+//! (shared resource: `Transaction` or `Client`).
+
+use ::postgres::error::SqlState;
+use ::postgres::types::ToSql;
+use ::postgres::{GenericClient, Row, Transaction};
+
+use crate::item::RawItem;
+use crate::{bucket::Bucket, count::Count, datetime::DateTime, evt::Event};
+
+use crate::kvstore::conflict::{ConflictPolicy, ConflictUpsert};
+use crate::kvstore::count::Counter;
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{DeleteRange, DeleteRow, DropBucket};
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::list::{ListBuckets, ListKeys, ListKeysAfter};
+use crate::kvstore::transaction::Transactional;
+use crate::kvstore::upsert::{self, BatchUpsert, UpsertRaw};
+
+fn bucket2create(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {} (
+                key BYTEA,
+                val BYTEA,
+                CONSTRAINT {}_pkc PRIMARY KEY(key)
+            )
+        "#,
+        name, name,
+    ))
+}
+
+fn bucket2upsert(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            INSERT INTO {} AS tgt
+            VALUES($1::BYTEA, $2::BYTEA)
+            ON CONFLICT ON CONSTRAINT {}_pkc
+            DO UPDATE
+            SET val = EXCLUDED.val
+            WHERE tgt.val <> EXCLUDED.val
+        "#,
+        name, name,
+    ))
+}
+
+/// Max number of rows in a multi-row upsert(2 parameters per row).
+const BATCH_ROWS: usize = 1000;
+
+fn bucket2upsert_batch(b: &Bucket, rows: usize) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    let values: Vec<String> = (0..rows)
+        .map(|r: usize| format!("(${}::BYTEA, ${}::BYTEA)", 2 * r + 1, 2 * r + 2))
+        .collect();
+    Ok(format!(
+        r#"
+            INSERT INTO {} AS tgt
+            VALUES {}
+            ON CONFLICT ON CONSTRAINT {}_pkc
+            DO UPDATE
+            SET val = EXCLUDED.val
+            WHERE tgt.val <> EXCLUDED.val
+        "#,
+        name,
+        values.join(","),
+        name,
+    ))
+}
+
+fn bucket2insert(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            INSERT INTO {}
+            VALUES ($1::BYTEA, $2::BYTEA)
+            ON CONFLICT (key)
+            DO NOTHING
+        "#,
+        name,
+    ))
+}
+
+/// Converts the PostgreSQL error into an `Event`.
+fn err2event(message: &str) -> impl Fn(::postgres::Error) -> Event + '_ {
+    move |e: ::postgres::Error| {
+        let msg: String = format!("{}: {}", message, e);
+        let retriable: &[SqlState] = &[
+            SqlState::T_R_SERIALIZATION_FAILURE,
+            SqlState::T_R_DEADLOCK_DETECTED,
+        ];
+        let timeout: &[SqlState] = &[SqlState::QUERY_CANCELED, SqlState::LOCK_NOT_AVAILABLE];
+        match (e.is_closed(), e.code()) {
+            (true, _) => Event::backend(msg, true, e),
+            (_, Some(s)) if SqlState::UNDEFINED_TABLE.eq(s) => Event::BucketNotFound(msg),
+            (_, Some(s)) if SqlState::UNIQUE_VIOLATION.eq(s) => Event::Conflict(msg),
+            (_, Some(s)) if timeout.contains(s) => Event::Timeout(msg),
+            (_, Some(s)) if retriable.contains(s) => Event::backend(msg, true, e),
+            _ => Event::backend(msg, false, e),
+        }
+    }
+}
+
+fn row2bytes(r: &Row) -> Result<Vec<u8>, Event> {
+    r.try_get(0)
+        .map_err(err2event("Unable to get bytes from a row"))
+}
+
+fn row2name(r: &Row) -> Result<Bucket, Event> {
+    r.try_get(0)
+        .map(|s: String| Bucket::from(s))
+        .map_err(err2event("Unable to get a bucket name"))
+}
+
+fn row2count(r: &Row) -> Result<u64, Event> {
+    let i: i64 = r.try_get(0).map_err(err2event("Unable to get count"))?;
+    u64::try_from(i).map_err(|e| Event::UnexpectedError(format!("Count out of range: {}", e)))
+}
+
+/// Creates a bucket(table) if not exists.
+pub fn create<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let query: String = bucket2create(b)?;
+    c.execute(query.as_str(), &[])
+        .map_err(err2event("Unable to create a bucket"))
+}
+
+/// Upserts an item(the value will not be updated if not changed).
+pub fn upsert<C>(c: &mut C, b: &Bucket, i: &RawItem) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let query: String = bucket2upsert(b)?;
+    let key: &[u8] = i.as_key();
+    let val: &[u8] = i.as_val();
+    c.execute(query.as_str(), &[&key, &val])
+        .map_err(err2event("Unable to upsert"))
+}
+
+/// Upserts items using multi-row INSERT statements.
+///
+/// Items with the same key will be deduplicated(the last one wins) because a row can not be
+/// updated twice by a statement.
+pub fn upsert_batch<C>(c: &mut C, b: &Bucket, items: &[RawItem]) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let uniq: Vec<&RawItem> = upsert::dedup_by_key(items);
+    uniq.chunks(BATCH_ROWS)
+        .try_fold(0, |tot, chunk: &[&RawItem]| {
+            let query: String = bucket2upsert_batch(b, chunk.len())?;
+            let params: Vec<&(dyn ToSql + Sync)> = chunk
+                .iter()
+                .flat_map(|i: &&RawItem| [i.as_key() as &(dyn ToSql + Sync), i.as_val()])
+                .collect();
+            c.execute(query.as_str(), &params)
+                .map_err(err2event("Unable to upsert"))
+                .map(|cnt| cnt + tot)
+        })
+}
+
+/// Inserts an item if the key does not exist(the existing value will be kept).
+pub fn insert_or_keep<C>(c: &mut C, b: &Bucket, i: &RawItem) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let query: String = bucket2insert(b)?;
+    let key: &[u8] = i.as_key();
+    let val: &[u8] = i.as_val();
+    c.execute(query.as_str(), &[&key, &val])
+        .map_err(err2event("Unable to insert"))
+}
+
+/// Tries to get a value from a bucket.
+pub fn get<C>(c: &mut C, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT val FROM {}
+            WHERE key=$1::BYTEA
+            LIMIT 1
+        "#,
+        name,
+    );
+    let row: Option<Row> = c
+        .query_opt(query.as_str(), &[&key])
+        .map_err(err2event("Unable to try to get a row"))?;
+    row.as_ref().map(row2bytes).transpose()
+}
+
+/// Tries to get a value from a bucket and locks the row(`SELECT ... FOR UPDATE`).
+///
+/// The row will be locked until the end of the transaction.
+pub fn get_for_update<C>(c: &mut C, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT val FROM {}
+            WHERE key=$1::BYTEA
+            LIMIT 1
+            FOR UPDATE
+        "#,
+        name,
+    );
+    let row: Option<Row> = c
+        .query_opt(query.as_str(), &[&key])
+        .map_err(err2event("Unable to try to get a row"))?;
+    row.as_ref().map(row2bytes).transpose()
+}
+
+/// Upserts an item using the policy while the existing row is locked.
+///
+/// - An existing row is locked by `get_for_update` before resolving.
+/// - A missing row is inserted using `insert_or_keep`.
+/// - A row inserted by other transaction meanwhile will be locked and resolved.
+pub fn upsert_resolved_locked<C>(
+    c: &mut C,
+    b: &Bucket,
+    i: &RawItem,
+    policy: &ConflictPolicy,
+) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let key: &[u8] = i.as_key();
+    let existing: Option<Vec<u8>> = match get_for_update(c, b, key)? {
+        Some(v) => Some(v),
+        None => match policy.resolve(None, i.as_val()) {
+            None => return Ok(0),
+            Some(val) => match insert_or_keep(c, b, &RawItem::new(key.to_vec(), val))? {
+                0 => get_for_update(c, b, key)?,
+                cnt => return Ok(cnt),
+            },
+        },
+    };
+    match policy.resolve(existing.as_deref(), i.as_val()) {
+        None => Ok(0),
+        Some(val) => upsert(c, b, &RawItem::new(key.to_vec(), val)),
+    }
+}
+
+/// Checks if the bucket exists.
+pub fn chk<C>(c: &mut C, b: &Bucket) -> Result<bool, Event>
+where
+    C: GenericClient,
+{
+    let bs: &str = b.as_str();
+    let query: &str = r#"
+        SELECT 1::INTEGER
+        FROM information_schema.tables
+        WHERE
+            table_schema='public'
+            AND table_name=$1::TEXT
+        LIMIT 1
+    "#;
+    c.query_opt(query, &[&bs])
+        .map_err(err2event("Unable to check table count"))
+        .map(|o: Option<Row>| o.is_some())
+}
+
+/// Gets all buckets.
+pub fn list<C>(c: &mut C) -> Result<Vec<Bucket>, Event>
+where
+    C: GenericClient,
+{
+    let rows: Vec<Row> = c
+        .query(
+            r#"
+                SELECT table_name::TEXT
+                FROM information_schema.tables
+                WHERE table_schema='public'
+                ORDER BY table_name
+            "#,
+            &[],
+        )
+        .map_err(err2event("Unable to get list of buckets"))?;
+    rows.iter().map(row2name).collect()
+}
+
+/// Gets all keys from a bucket.
+pub fn list_keys<C>(c: &mut C, b: &Bucket) -> Result<Vec<Vec<u8>>, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT key FROM {}
+            ORDER BY key
+        "#,
+        name,
+    );
+    let rows: Vec<Row> = c
+        .query(query.as_str(), &[])
+        .map_err(err2event("Unable to get keys"))?;
+    rows.iter().map(row2bytes).collect()
+}
+
+/// Gets at most `limit` keys which are greater than `after` from a bucket.
+pub fn list_keys_after<C>(
+    c: &mut C,
+    b: &Bucket,
+    after: Option<&[u8]>,
+    limit: u32,
+) -> Result<Vec<Vec<u8>>, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT key FROM {}
+            WHERE $1::BYTEA IS NULL OR key > $1
+            ORDER BY key
+            LIMIT $2
+        "#,
+        name,
+    );
+    let lim: i64 = limit.into();
+    let rows: Vec<Row> = c
+        .query(query.as_str(), &[&after, &lim])
+        .map_err(err2event("Unable to get keys"))?;
+    rows.iter().map(row2bytes).collect()
+}
+
+/// Counts number of rows in a bucket.
+pub fn count<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT COUNT(*)::BIGINT FROM {}
+        "#,
+        name,
+    );
+    let row: Row = c
+        .query_one(query.as_str(), &[])
+        .map_err(err2event("Unable to get table count"))?;
+    row2count(&row)
+}
+
+/// Drops a bucket if exists(returns 1 if dropped).
+pub fn drop_bucket<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let exists: bool = chk(c, b)?;
+    let query: String = format!(
+        r#"
+            DROP TABLE IF EXISTS {}
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), &[])
+        .map_err(err2event("Unable to drop a bucket"))
+        .map(|_| u64::from(exists))
+}
+
+/// Deletes a row from a bucket.
+pub fn delete<C>(c: &mut C, b: &Bucket, key: &[u8]) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            DELETE FROM {}
+            WHERE key = $1::BYTEA
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), &[&key])
+        .map_err(err2event("Unable to delete a row"))
+}
+
+/// Deletes rows in the key range(lower: inclusive, upper: exclusive).
+pub fn delete_range<C>(c: &mut C, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event>
+where
+    C: GenericClient,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            DELETE FROM {}
+            WHERE $1::BYTEA <= key AND key < $2::BYTEA
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), &[&lower, &upper])
+        .map_err(err2event("Unable to delete rows"))
+}
+
+/// Commits changes.
+pub fn commit(t: Transaction) -> Result<(), Event> {
+    t.commit().map_err(err2event("Unable to commit changes"))
+}
+
+/// Savepoint name used by `Transactional`.
+const SAVEPOINT: &str = "kv2spacetimedb";
+
+/// Uses a savepoint(nested transactions use nested savepoints).
+impl Transactional for Transaction<'_> {
+    fn begin(&mut self) -> Result<(), Event> {
+        let query: String = format!("SAVEPOINT {}", SAVEPOINT);
+        self.batch_execute(query.as_str())
+            .map_err(err2event("Unable to create a savepoint"))
+    }
+
+    fn commit(&mut self) -> Result<(), Event> {
+        let query: String = format!("RELEASE SAVEPOINT {}", SAVEPOINT);
+        self.batch_execute(query.as_str())
+            .map_err(err2event("Unable to release a savepoint"))
+    }
+
+    fn rollback(&mut self) -> Result<(), Event> {
+        let query: String = format!(
+            "ROLLBACK TO SAVEPOINT {0}; RELEASE SAVEPOINT {0}",
+            SAVEPOINT
+        );
+        self.batch_execute(query.as_str())
+            .map_err(err2event("Unable to rollback to a savepoint"))
+    }
+}
+
+impl Create for Transaction<'_> {
+    fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        create(self, b)
+    }
+}
+
+impl UpsertRaw for Transaction<'_> {
+    fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        upsert(self, b, i)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        commit(self)
+    }
+}
+
+impl BatchUpsert for Transaction<'_> {
+    fn upsert_batch(&mut self, b: &Bucket, items: &[RawItem]) -> Result<u64, Event> {
+        upsert_batch(self, b, items)
+    }
+}
+
+impl ConflictUpsert for Transaction<'_> {
+    fn upsert_with_policy(
+        &mut self,
+        b: &Bucket,
+        i: &RawItem,
+        policy: &ConflictPolicy,
+    ) -> Result<u64, Event> {
+        match policy {
+            ConflictPolicy::Overwrite => upsert(self, b, i),
+            ConflictPolicy::KeepExisting => insert_or_keep(self, b, i),
+            _ => upsert_resolved_locked(self, b, i, policy),
+        }
+    }
+}
+
+impl GetRaw for Transaction<'_> {
+    fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        get(self, b, key)
+    }
+
+    fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        chk(self, b)
+    }
+}
+
+impl ListBuckets for Transaction<'_> {
+    fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        list(self)
+    }
+}
+
+impl ListKeys<Vec<u8>> for Transaction<'_> {
+    fn list(&mut self, b: &Bucket) -> Result<Vec<Vec<u8>>, Event> {
+        list_keys(self, b)
+    }
+}
+
+impl ListKeysAfter for Transaction<'_> {
+    fn list_after(
+        &mut self,
+        b: &Bucket,
+        after: Option<&[u8]>,
+        limit: u32,
+    ) -> Result<Vec<Vec<u8>>, Event> {
+        list_keys_after(self, b, after, limit)
+    }
+}
+
+impl Counter for Transaction<'_> {
+    fn count(&mut self, b: &Bucket) -> Result<Count, Event> {
+        let cnt: u64 = count(self, b)?;
+        let dt: DateTime = DateTime::time_source_new_std()()?;
+        Ok(Count::new(cnt, dt))
+    }
+}
+
+impl DropBucket for Transaction<'_> {
+    fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        drop_bucket(self, b)
+    }
+}
+
+impl DeleteRow for Transaction<'_> {
+    fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        delete(self, b, key)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        commit(self)
+    }
+}
+
+impl DeleteRange for Transaction<'_> {
+    fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        delete_range(self, b, lower, upper)
+    }
+}
+
+// Requires a running PostgreSQL(see pgtest.sh).
+// Every test uses a transaction which will be rolled back.
+#[cfg(test)]
+mod test_postgres {
+
+    use std::env;
+
+    use ::postgres::{Client, Config, NoTls};
+
+    fn connect() -> Client {
+        Config::new()
+            .host(env::var("PGHOST").unwrap().as_str())
+            .port(
+                env::var("PGPORT")
+                    .ok()
+                    .and_then(|p| str::parse(p.as_str()).ok())
+                    .unwrap_or(5432),
+            )
+            .dbname(env::var("PGDATABASE").unwrap().as_str())
+            .user(env::var("PGUSER").unwrap().as_str())
+            .password(env::var("PGPASSWORD").unwrap_or_default())
+            .connect(NoTls)
+            .unwrap()
+    }
+
+    mod upsert_all_shared {
+        use ::postgres::{Client, Transaction};
+
+        use crate::item::Item;
+        use crate::kvstore::{postgres, upsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        #[ignore]
+        fn test_upsert() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let nested: Transaction = t.transaction().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let cnt: u64 = upsert::upsert_all_shared(
+                postgres::upsert,
+                postgres::create,
+                nested,
+                postgres::commit,
+                source.into_iter(),
+            )
+            .unwrap();
+            assert_eq!(cnt, 9);
+
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ));
+            let val: Option<Vec<u8>> = postgres::get(&mut t, &b, b"00:30:21.0Z").unwrap();
+            assert_eq!(val, Some(b"42".to_vec()));
+
+            let missing: Option<Vec<u8>> = postgres::get(&mut t, &b, b"00:30:22.0Z").unwrap();
+            assert_eq!(missing, None);
+
+            let devices: u64 = postgres::count(&mut t, &Bucket::new_devices_master()).unwrap();
+            assert_eq!(devices, 2);
+
+            let keys: Vec<Vec<u8>> =
+                postgres::list_keys(&mut t, &Bucket::new_dates_master()).unwrap();
+            assert_eq!(keys, vec![b"2022_11_02".to_vec()]);
+
+            t.rollback().unwrap();
+        }
+    }
+
+    mod upsert_batch {
+        use ::postgres::{Client, Transaction};
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::postgres;
+        use crate::kvstore::upsert::{self, BatchUpsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        #[ignore]
+        fn test_chunks() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::from(String::from("batch"));
+            postgres::create(&mut t, &b).unwrap();
+            let mut items: Vec<RawItem> = (0..2500u32)
+                .map(|n: u32| Item::new(n.to_be_bytes().to_vec(), b"old".to_vec()))
+                .collect();
+            items.push(Item::new(0u32.to_be_bytes().to_vec(), b"new".to_vec()));
+            let cnt: u64 = t.upsert_batch(&b, &items).unwrap();
+            assert_eq!(cnt, 2500);
+            let val: Option<Vec<u8>> = postgres::get(&mut t, &b, &0u32.to_be_bytes()).unwrap();
+            assert_eq!(val, Some(b"new".to_vec()));
+
+            // unchanged values are not updated(only the first key: new -> old)
+            let cnt: u64 = postgres::upsert_batch(&mut t, &b, &items[..10]).unwrap();
+            assert_eq!(cnt, 1);
+            t.rollback().unwrap();
+        }
+
+        #[test]
+        #[ignore]
+        fn test_upsert_all() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let mut upst =
+                |b: &Bucket, items: &[RawItem]| upsert::create_upsert_batch(&mut t, b, items);
+            let cnt: u64 = upsert::upsert_all_batch_ex(
+                source.into_iter(),
+                &mut upst,
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+            assert_eq!(cnt, 9);
+            let devices: u64 = postgres::count(&mut t, &Bucket::new_devices_master()).unwrap();
+            assert_eq!(devices, 2);
+            t.rollback().unwrap();
+        }
+    }
+
+    mod upsert_with_policy {
+        use ::postgres::{Client, Transaction};
+
+        use crate::bucket::Bucket;
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::conflict::{ConflictPolicy, ConflictUpsert};
+        use crate::kvstore::postgres;
+
+        #[test]
+        #[ignore]
+        fn test_keep_existing() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::from(String::from("policy"));
+            postgres::create(&mut t, &b).unwrap();
+            let first: RawItem = Item::new(b"k".to_vec(), b"first".to_vec());
+            let second: RawItem = Item::new(b"k".to_vec(), b"second".to_vec());
+            let keep = ConflictPolicy::KeepExisting;
+            assert_eq!(t.upsert_with_policy(&b, &first, &keep).unwrap(), 1);
+            assert_eq!(t.upsert_with_policy(&b, &second, &keep).unwrap(), 0);
+            let merge = ConflictPolicy::Merge(Box::new(|e: &[u8], u: &[u8]| [e, u].concat()));
+            assert_eq!(t.upsert_with_policy(&b, &second, &merge).unwrap(), 1);
+            let val: Option<Vec<u8>> = postgres::get(&mut t, &b, b"k").unwrap();
+            assert_eq!(val, Some(b"firstsecond".to_vec()));
+            let missing: RawItem = Item::new(b"m".to_vec(), b"new".to_vec());
+            assert_eq!(t.upsert_with_policy(&b, &missing, &merge).unwrap(), 1);
+            let val: Option<Vec<u8>> = postgres::get(&mut t, &b, b"m").unwrap();
+            assert_eq!(val, Some(b"new".to_vec()));
+            t.rollback().unwrap();
+        }
+    }
+
+    mod delete_stale_data_default {
+        use ::postgres::{Client, Transaction};
+
+        use crate::item::Item;
+        use crate::kvstore::{delete, postgres, upsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        #[ignore]
+        fn test_delete() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_01".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let nested: Transaction = t.transaction().unwrap();
+            upsert::upsert_all_shared(
+                postgres::upsert,
+                postgres::create,
+                nested,
+                postgres::commit,
+                source.into_iter(),
+            )
+            .unwrap();
+
+            let nested: Transaction = t.transaction().unwrap();
+            let cnt: u64 =
+                delete::delete_stale_data_default(nested, Date::new_unchecked("2022_11_02".into()))
+                    .unwrap();
+
+            // 2 buckets dropped(data, devices master), 2 rows deleted(dates masters).
+            assert_eq!(cnt, 4);
+
+            let stale: Bucket = Bucket::from(String::from(
+                "data_2022_11_01_cafef00ddeadbeafface864299792458",
+            ));
+            assert!(!postgres::chk(&mut t, &stale).unwrap());
+            let buckets: Vec<Bucket> = postgres::list(&mut t).unwrap();
+            assert!(buckets.contains(&Bucket::new_dates_master()));
+
+            t.rollback().unwrap();
+        }
+    }
+
+    mod list_keys_after {
+        use ::postgres::{Client, Transaction};
+
+        use crate::bucket::Bucket;
+        use crate::item::Item;
+        use crate::kvstore::postgres;
+
+        #[test]
+        #[ignore]
+        fn test_pages() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            postgres::create(&mut t, &b).unwrap();
+            postgres::delete_range(&mut t, &b, &[], &[0xff]).unwrap();
+            for d in ["2022_11_01", "2022_11_02", "2022_11_03"] {
+                let i = Item::new(d.as_bytes().to_vec(), vec![]);
+                postgres::upsert(&mut t, &b, &i).unwrap();
+            }
+            let first: Vec<Vec<u8>> = postgres::list_keys_after(&mut t, &b, None, 2).unwrap();
+            assert_eq!(first, vec![b"2022_11_01".to_vec(), b"2022_11_02".to_vec()]);
+            let last: Vec<Vec<u8>> =
+                postgres::list_keys_after(&mut t, &b, Some(b"2022_11_02"), 2).unwrap();
+            assert_eq!(last, vec![b"2022_11_03".to_vec()]);
+            t.rollback().unwrap();
+        }
+    }
+
+    mod transactional {
+        use ::postgres::{Client, Transaction};
+
+        use crate::bucket::Bucket;
+        use crate::evt::Event;
+        use crate::item::Item;
+        use crate::kvstore::postgres;
+        use crate::kvstore::transaction;
+
+        #[test]
+        #[ignore]
+        fn test_rollback() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::from(String::from("savepoint"));
+            postgres::create(&mut t, &b).unwrap();
+            let kept = Item::new(b"2022_11_01".to_vec(), vec![]);
+            postgres::upsert(&mut t, &b, &kept).unwrap();
+
+            let r: Result<u64, Event> = transaction::transaction(&mut t, |t: &mut Transaction| {
+                postgres::upsert(t, &b, &Item::new(b"2022_11_02".to_vec(), vec![]))?;
+                let missing: Bucket = Bucket::from(String::from("missing_savepoint"));
+                postgres::upsert(t, &missing, &Item::new(b"dev".to_vec(), vec![]))
+            });
+            assert!(r.is_err());
+            let keys: Vec<Vec<u8>> = postgres::list_keys(&mut t, &b).unwrap();
+            assert_eq!(keys, vec![b"2022_11_01".to_vec()]);
+            t.rollback().unwrap();
+        }
+    }
+
+    mod with_namer {
+        use ::postgres::{Client, Transaction};
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::{delete, get, get::GetRaw, postgres, upsert};
+        use crate::namer::{BucketNamer, SimpleBucketNamer};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        #[ignore]
+        fn test_prefixed() {
+            let mut c: Client = super::connect();
+            let mut t: Transaction = c.transaction().unwrap();
+            let namer = SimpleBucketNamer::default()
+                .with_prefix("telemetry_".into())
+                .unwrap()
+                .with_device_first(true);
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date = Date::new_unchecked("2022_11_02".into());
+            let source = vec![Data::new(
+                dev.clone(),
+                date.clone(),
+                Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+            )];
+            let mut nested: Transaction = t.transaction().unwrap();
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut nested, b, i);
+            upsert::upsert_all_with_namer(
+                source.into_iter(),
+                &mut cu,
+                upsert::upsert_value_generator_new_func_default(),
+                &namer,
+            )
+            .unwrap();
+
+            let mut g = |b: &Bucket, k: &[u8]| GetRaw::get(&mut nested, b, k);
+            let found: Option<RawItem> =
+                get::get_raw_with_namer(&mut g, &dev, &date, b"00:30:21.0Z", &namer).unwrap();
+            assert_eq!(found.map(|i| i.as_val().to_vec()), Some(b"42".to_vec()));
+
+            let cnt: u64 = delete::delete_device_with_namer(nested, dev.clone(), &namer).unwrap();
+
+            // 2 buckets dropped, 2 rows deleted(devices, devices4date).
+            assert_eq!(cnt, 4);
+            assert!(postgres::chk(&mut t, &namer.dates()).unwrap());
+            assert!(!postgres::chk(&mut t, &namer.dates4device(&dev)).unwrap());
+            t.rollback().unwrap();
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Rebuilds master buckets from existing data buckets.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::bucket::{Bucket, BucketKind};
+use crate::item::{Item, RawItem};
+use crate::namer::{BucketNamer, SimpleBucketNamer};
+use crate::{date::Date, device::Device, evt::Event};
+
+use crate::kvstore::delete::DeleteRow;
+use crate::kvstore::fsck::MasterRow;
+use crate::kvstore::list::{ListBuckets, ListKeys};
+use crate::kvstore::upsert::UpsertValueGenerator;
+
+fn data2masters<I, G, N>(data: I, upsert_value_gen: &G, namer: &N) -> BTreeMap<Bucket, Vec<RawItem>>
+where
+    I: Iterator<Item = (Date, Device)>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    data.flat_map(|(date, dev)| {
+        [
+            (
+                namer.dates4device(&dev),
+                upsert_value_gen.dates4device(&date),
+            ),
+            (
+                namer.devices4date(&date),
+                upsert_value_gen.devices4date(&dev),
+            ),
+            (namer.dates(), upsert_value_gen.dates(&date)),
+            (namer.devices(), upsert_value_gen.devices(&dev)),
+        ]
+    })
+    .fold(BTreeMap::new(), |mut m, (b, i)| {
+        m.entry(b).or_insert_with(Vec::new).push(i);
+        m
+    })
+}
+
+fn data_buckets<'a, N>(
+    buckets: &'a [Bucket],
+    namer: &'a N,
+) -> impl Iterator<Item = (Date, Device)> + 'a
+where
+    N: BucketNamer,
+{
+    buckets
+        .iter()
+        .flat_map(|b: &Bucket| namer.parse(b).ok())
+        .filter_map(|k: BucketKind| match k {
+            BucketKind::Data { date, device } => Some((date, device)),
+            _ => None,
+        })
+}
+
+/// Upserts master rows for all data buckets named by the namer.
+///
+/// Existing master rows will not be removed(see `prune_masters_with_namer`).
+///
+/// # Arguments
+/// - store: Gets list of buckets.
+/// - upsert: Upserts a master row(e.g, `create_upsert`).
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Parses/creates bucket names.
+pub fn rebuild_masters_with_namer<S, U, G, N>(
+    store: &mut S,
+    upsert: &mut U,
+    upsert_value_gen: &G,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    S: ListBuckets,
+    U: FnMut(&mut S, &Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    let buckets: Vec<Bucket> = store.list()?;
+    let data = data_buckets(&buckets, namer);
+    let masters: BTreeMap<Bucket, Vec<RawItem>> = data2masters(data, upsert_value_gen, namer);
+    masters.into_iter().try_fold(0, |tot, (b, v)| {
+        let uniq: Vec<RawItem> = Item::uniq(v);
+        uniq.iter().try_fold(tot, |t, i: &RawItem| {
+            upsert(store, &b, i).map(|cnt| cnt + t)
+        })
+    })
+}
+
+/// Upserts master rows for all data buckets using the default namer.
+///
+/// # Arguments
+/// - store: Gets list of buckets.
+/// - upsert: Upserts a master row(e.g, `create_upsert`).
+/// - upsert_value_gen: Value generator for master buckets.
+pub fn rebuild_masters<S, U, G>(
+    store: &mut S,
+    upsert: &mut U,
+    upsert_value_gen: &G,
+) -> Result<u64, Event>
+where
+    S: ListBuckets,
+    U: FnMut(&mut S, &Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    rebuild_masters_with_namer(
+        store,
+        upsert,
+        upsert_value_gen,
+        &SimpleBucketNamer::default(),
+    )
+}
+
+/// Deletes master rows which refer to no data bucket and finalizes.
+///
+/// Expected master keys are generated by the value generator(same as `rebuild_masters_with_namer`).
+///
+/// # Arguments
+/// - store: Gets list of buckets/keys, Deletes a row.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Parses/creates bucket names.
+pub fn prune_masters_with_namer<S, G, N>(
+    mut store: S,
+    upsert_value_gen: &G,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    S: ListBuckets + ListKeys<Vec<u8>> + DeleteRow,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    let buckets: Vec<Bucket> = ListBuckets::list(&mut store)?;
+    let data = data_buckets(&buckets, namer);
+    let expected: BTreeMap<Bucket, BTreeSet<Vec<u8>>> = data2masters(data, upsert_value_gen, namer)
+        .into_iter()
+        .map(|(b, v)| (b, v.into_iter().map(|i| i.into_pair().0).collect()))
+        .collect();
+    let masters = buckets.iter().filter(|b: &&Bucket| {
+        matches!(
+            namer.parse(b),
+            Ok(BucketKind::Dates)
+                | Ok(BucketKind::Devices)
+                | Ok(BucketKind::DatesForDevice(_))
+                | Ok(BucketKind::DevicesForDate(_))
+        )
+    });
+    let mut dangling: Vec<MasterRow> = vec![];
+    for b in masters {
+        let keys: Vec<Vec<u8>> = ListKeys::list(&mut store, b)?;
+        let found = |key: &Vec<u8>| -> bool {
+            expected
+                .get(b)
+                .map(|e: &BTreeSet<Vec<u8>>| e.contains(key))
+                .unwrap_or(false)
+        };
+        let stale = keys.into_iter().filter(|key| !found(key));
+        dangling.extend(stale.map(|key: Vec<u8>| (b.clone(), key)));
+    }
+    let cnt: u64 = dangling.iter().try_fold(0, |tot, (b, key): &MasterRow| {
+        store.delete(b, key).map(|cnt| cnt + tot)
+    })?;
+    store.finalize()?;
+    Ok(cnt)
+}
+
+/// Deletes master rows which refer to no data bucket using the default namer.
+///
+/// # Arguments
+/// - store: Gets list of buckets/keys, Deletes a row.
+/// - upsert_value_gen: Value generator for master buckets.
+pub fn prune_masters<S, G>(store: S, upsert_value_gen: &G) -> Result<u64, Event>
+where
+    S: ListBuckets + ListKeys<Vec<u8>> + DeleteRow,
+    G: UpsertValueGenerator,
+{
+    prune_masters_with_namer(store, upsert_value_gen, &SimpleBucketNamer::default())
+}
+
+/// Upserts master rows for all data buckets, then deletes stale master rows(optional).
+///
+/// # Arguments
+/// - store: Gets list of buckets/keys, Deletes a row.
+/// - upsert: Upserts a master row(e.g, `create_upsert`).
+/// - upsert_value_gen: Value generator for master buckets.
+/// - prune: Deletes master rows which refer to no data bucket if true.
+///
+/// # Returns
+/// Total of upsert results and number of deleted rows.
+pub fn rebuild_masters_default<S, U, G>(
+    mut store: S,
+    upsert: &mut U,
+    upsert_value_gen: &G,
+    prune: bool,
+) -> Result<(u64, u64), Event>
+where
+    S: ListBuckets + ListKeys<Vec<u8>> + DeleteRow,
+    U: FnMut(&mut S, &Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    let upserted: u64 = rebuild_masters(&mut store, upsert, upsert_value_gen)?;
+    let deleted: u64 = match prune {
+        true => prune_masters(store, upsert_value_gen)?,
+        false => {
+            store.finalize()?;
+            0
+        }
+    };
+    Ok((upserted, deleted))
+}
+
+#[cfg(test)]
+mod test_rebuild {
+
+    mod rebuild_masters_default {
+        use crate::item::Item;
+        use crate::kvstore::create::Create;
+        use crate::kvstore::delete::{DeleteRow, DropBucket};
+        use crate::kvstore::fsck;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::rebuild;
+        use crate::kvstore::upsert::{self, UpsertRaw};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        const DEV: &str = "cafef00ddeadbeafface864299792458";
+
+        fn store() -> MemStore {
+            let mut m: MemStore = MemStore::new();
+            let source = ["2022_11_01", "2022_11_02"].into_iter().map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked(DEV.into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            });
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all(source, &mut cu).unwrap();
+            m
+        }
+
+        #[test]
+        fn test_recover() {
+            let mut m: MemStore = store();
+            let expected: Vec<_> = ["dates", "devices", "devices_2022_11_01"]
+                .into_iter()
+                .map(|s: &str| m.items(&Bucket::from(String::from(s))).unwrap())
+                .collect();
+
+            // lost masters(e.g, crash after data upsert)
+            DropBucket::drop(&mut m, &Bucket::new_dates_master()).unwrap();
+            DropBucket::drop(&mut m, &Bucket::from(String::from("devices_2022_11_01"))).unwrap();
+            DeleteRow::delete(&mut m, &Bucket::new_devices_master(), DEV.as_bytes()).unwrap();
+
+            // stale master row
+            let stale: Bucket = Bucket::from(String::from("devices_2022_10_31"));
+            Create::create(&mut m, &stale).unwrap();
+            UpsertRaw::upsert(&mut m, &stale, &Item::new(DEV.into(), vec![])).unwrap();
+            assert!(!fsck::fsck(&mut m).unwrap().is_consistent());
+
+            let gen = upsert::upsert_value_generator_new_func_default();
+            let (upserted, deleted) =
+                rebuild::rebuild_masters_default(&mut m, &mut upsert::create_upsert, &gen, true)
+                    .unwrap();
+            assert_eq!(upserted, 6);
+            assert_eq!(deleted, 1);
+            assert!(fsck::fsck(&mut m).unwrap().is_consistent());
+            let got: Vec<_> = ["dates", "devices", "devices_2022_11_01"]
+                .into_iter()
+                .map(|s: &str| m.items(&Bucket::from(String::from(s))).unwrap())
+                .collect();
+            assert_eq!(got, expected);
+            assert_eq!(m.count(&stale).unwrap(), 0);
+        }
+
+        #[test]
+        fn test_no_prune() {
+            let mut m: MemStore = store();
+            let stale: Bucket = Bucket::from(String::from("devices_2022_10_31"));
+            Create::create(&mut m, &stale).unwrap();
+            UpsertRaw::upsert(&mut m, &stale, &Item::new(DEV.into(), vec![])).unwrap();
+
+            let gen = upsert::upsert_value_generator_new_func_default();
+            let (_, deleted) =
+                rebuild::rebuild_masters_default(&mut m, &mut upsert::create_upsert, &gen, false)
+                    .unwrap();
+            assert_eq!(deleted, 0);
+            assert_eq!(m.count(&stale).unwrap(), 1);
+        }
+
+        #[test]
+        fn test_custom_generator() {
+            let mut m: MemStore = MemStore::new();
+            let gen = upsert::upsert_value_generator_new_func(
+                |d: &Date| Item::new(d.as_bytes().to_vec(), b"dates".to_vec()),
+                |d: &Device| Item::new(format!("dev:{}", d.as_str()).into_bytes(), vec![]),
+                |d: &Date| Item::new(format!("date:{}", d.as_str()).into_bytes(), vec![]),
+                |d: &Device| Item::new(d.as_bytes().to_vec(), b"devices4date".to_vec()),
+            );
+            let source = ["2022_11_01", "2022_11_02"].into_iter().map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked(DEV.into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            });
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut m, b, i);
+            upsert::upsert_all_ex(source, &mut cu, &gen).unwrap();
+            let dates4device: Bucket = Bucket::from(format!("dates_{}", DEV));
+            let expected = m.items(&dates4device).unwrap();
+
+            let stale: Bucket = Bucket::new_devices_master();
+            UpsertRaw::upsert(&mut m, &stale, &Item::new(b"dev:gone".to_vec(), vec![])).unwrap();
+
+            let (_, deleted) =
+                rebuild::rebuild_masters_default(&mut m, &mut upsert::create_upsert, &gen, true)
+                    .unwrap();
+            assert_eq!(deleted, 1);
+            assert_eq!(m.items(&dates4device).unwrap(), expected);
+            assert_eq!(m.count(&stale).unwrap(), 1);
+            assert_eq!(m.count(&Bucket::new_dates_master()).unwrap(), 2);
+        }
+    }
+}
@@ -0,0 +1,963 @@
+//! SQLite backend(requires `sqlite` feature).
+//!
+//! Each bucket is a table which has a BLOB key(primary key) and a BLOB value.
+//! Bucket names which are not safe identifiers will be rejected(`Event::UnsafeIdentifier`).
+//!
+//! The functions can be used as closures for functions like
+//! [`upsert_all_shared`](crate::kvstore::upsert::upsert_all_shared)
+//! (shared resource: `Transaction` or `&Connection`).
+
+use std::ops::Deref;
+
+use rusqlite::{
+    params, params_from_iter, Connection, ErrorCode, OptionalExtension, Row, Transaction,
+};
+
+use crate::item::RawItem;
+use crate::{bucket::Bucket, count::Count, datetime::DateTime, evt::Event};
+
+use crate::kvstore::conflict::{self, ConflictPolicy, ConflictUpsert};
+use crate::kvstore::count::Counter;
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{DeleteRange, DeleteRow, DropBucket};
+use crate::kvstore::get::GetRaw;
+use crate::kvstore::list::{ListBuckets, ListKeys, ListKeysAfter};
+use crate::kvstore::transaction::Transactional;
+use crate::kvstore::upsert::{self, BatchUpsert, UpsertRaw};
+
+fn bucket2create(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            CREATE TABLE IF NOT EXISTS {} (
+                key BLOB,
+                val BLOB,
+                CONSTRAINT {}_pkc PRIMARY KEY(key)
+            )
+        "#,
+        name, name,
+    ))
+}
+
+fn bucket2upsert(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            INSERT INTO {}
+            VALUES (?1, ?2)
+            ON CONFLICT (key)
+            DO UPDATE
+            SET val=EXCLUDED.val
+            WHERE {}.val <> EXCLUDED.val
+        "#,
+        name, name,
+    ))
+}
+
+/// Max number of rows in a multi-row upsert(2 parameters per row).
+const BATCH_ROWS: usize = 400;
+
+fn bucket2upsert_batch(b: &Bucket, rows: usize) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    let values: Vec<&str> = vec!["(?, ?)"; rows];
+    Ok(format!(
+        r#"
+            INSERT INTO {}
+            VALUES {}
+            ON CONFLICT (key)
+            DO UPDATE
+            SET val=EXCLUDED.val
+            WHERE {}.val <> EXCLUDED.val
+        "#,
+        name,
+        values.join(","),
+        name,
+    ))
+}
+
+fn bucket2insert(b: &Bucket) -> Result<String, Event> {
+    let name: &str = b.as_safe_identifier()?;
+    Ok(format!(
+        r#"
+            INSERT INTO {}
+            VALUES (?1, ?2)
+            ON CONFLICT (key)
+            DO NOTHING
+        "#,
+        name,
+    ))
+}
+
+/// Converts the SQLite error into an `Event`.
+fn err2event(message: &str) -> impl Fn(rusqlite::Error) -> Event + '_ {
+    move |e: rusqlite::Error| {
+        let msg: String = format!("{}: {}", message, e);
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => {
+                Event::backend(msg, true, e)
+            }
+            Some(ErrorCode::OperationInterrupted) => Event::Timeout(msg),
+            Some(ErrorCode::ConstraintViolation) => Event::Conflict(msg),
+            _ => Event::backend(msg, false, e),
+        }
+    }
+}
+
+/// Converts the SQLite error into an `Event`(`BucketNotFound` if the bucket does not exist).
+///
+/// SQLite reports a missing table as a generic error(`SQLITE_ERROR`),
+/// so the existence of the table will be checked for generic errors.
+fn bucket_err2event<'a>(
+    c: &'a Connection,
+    b: &'a Bucket,
+    message: &'a str,
+) -> impl Fn(rusqlite::Error) -> Event + 'a {
+    move |e: rusqlite::Error| {
+        let generic: bool = matches!(e.sqlite_error_code(), Some(ErrorCode::Unknown));
+        let missing: bool = generic && !table_exists(c, b).unwrap_or(true);
+        match missing {
+            true => Event::BucketNotFound(format!("{}: {}", message, e)),
+            false => err2event(message)(e),
+        }
+    }
+}
+
+fn table_exists(c: &Connection, b: &Bucket) -> Result<bool, Event> {
+    c.query_row(
+        r#"
+            SELECT COUNT(*) FROM sqlite_master
+            WHERE type='table' AND name=?1
+        "#,
+        params![b.as_str()],
+        |r: &Row| r.get::<_, i64>(0),
+    )
+    .map_err(err2event("Unable to check a bucket"))
+    .map(|cnt: i64| 0 < cnt)
+}
+
+fn row2bytes(r: &Row) -> rusqlite::Result<Vec<u8>> {
+    r.get(0)
+}
+
+/// Creates a bucket(table) if not exists.
+///
+/// Always returns 0(SQLite does not report changes for DDL).
+pub fn create<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let query: String = bucket2create(b)?;
+    c.execute(query.as_str(), [])
+        .map_err(err2event("Unable to create a bucket"))
+        .map(|_| 0)
+}
+
+/// Upserts an item(the value will not be updated if not changed).
+pub fn upsert<C>(c: &mut C, b: &Bucket, i: &RawItem) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let query: String = bucket2upsert(b)?;
+    let key: &[u8] = i.as_key();
+    let val: &[u8] = i.as_val();
+    c.execute(query.as_str(), params![key, val])
+        .map_err(bucket_err2event(c, b, "Unable to upsert"))
+        .map(|cnt| cnt as u64)
+}
+
+/// Upserts items using multi-row INSERT statements.
+///
+/// Items with the same key will be deduplicated(the last one wins).
+pub fn upsert_batch<C>(c: &mut C, b: &Bucket, items: &[RawItem]) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let uniq: Vec<&RawItem> = upsert::dedup_by_key(items);
+    uniq.chunks(BATCH_ROWS)
+        .try_fold(0, |tot, chunk: &[&RawItem]| {
+            let query: String = bucket2upsert_batch(b, chunk.len())?;
+            let values = chunk
+                .iter()
+                .flat_map(|i: &&RawItem| [i.as_key().as_slice(), i.as_val().as_slice()]);
+            c.execute(query.as_str(), params_from_iter(values))
+                .map_err(bucket_err2event(c, b, "Unable to upsert"))
+                .map(|cnt| cnt as u64 + tot)
+        })
+}
+
+/// Inserts an item if the key does not exist(the existing value will be kept).
+pub fn insert_or_keep<C>(c: &mut C, b: &Bucket, i: &RawItem) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let query: String = bucket2insert(b)?;
+    let key: &[u8] = i.as_key();
+    let val: &[u8] = i.as_val();
+    c.execute(query.as_str(), params![key, val])
+        .map_err(bucket_err2event(c, b, "Unable to insert"))
+        .map(|cnt| cnt as u64)
+}
+
+/// Tries to get a value from a bucket.
+pub fn get<C>(c: &mut C, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT val FROM {}
+            WHERE key=?1
+            LIMIT 1
+        "#,
+        name,
+    );
+    c.query_row(query.as_str(), params![key], row2bytes)
+        .optional()
+        .map_err(bucket_err2event(c, b, "Unable to try to get a row"))
+}
+
+/// Checks if the bucket exists.
+pub fn chk<C>(c: &mut C, b: &Bucket) -> Result<bool, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    table_exists(c, b)
+}
+
+/// Gets all buckets.
+pub fn list<C>(c: &mut C) -> Result<Vec<Bucket>, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let mut s = c
+        .prepare(
+            r#"
+                SELECT name FROM sqlite_master
+                WHERE type='table' AND name NOT LIKE 'sqlite\_%' ESCAPE '\'
+                ORDER BY name
+            "#,
+        )
+        .map_err(err2event("Unable to prepare"))?;
+    let rows = s
+        .query_map([], |r: &Row| r.get::<_, String>(0))
+        .map_err(err2event("Unable to get list of table names"))?;
+    rows.map(|r| {
+        r.map(Bucket::from)
+            .map_err(err2event("Unable to get a row"))
+    })
+    .collect()
+}
+
+/// Gets all keys from a bucket.
+pub fn list_keys<C>(c: &mut C, b: &Bucket) -> Result<Vec<Vec<u8>>, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT key FROM {}
+            ORDER BY key
+        "#,
+        name,
+    );
+    let mut s = c
+        .prepare(query.as_str())
+        .map_err(bucket_err2event(c, b, "Unable to prepare"))?;
+    let rows = s
+        .query_map([], row2bytes)
+        .map_err(bucket_err2event(c, b, "Unable to get keys"))?;
+    rows.map(|r| r.map_err(err2event("Unable to get a row")))
+        .collect()
+}
+
+/// Gets at most `limit` keys which are greater than `after` from a bucket.
+pub fn list_keys_after<C>(
+    c: &mut C,
+    b: &Bucket,
+    after: Option<&[u8]>,
+    limit: u32,
+) -> Result<Vec<Vec<u8>>, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT key FROM {}
+            WHERE ?1 IS NULL OR key > ?1
+            ORDER BY key
+            LIMIT ?2
+        "#,
+        name,
+    );
+    let mut s = c
+        .prepare(query.as_str())
+        .map_err(bucket_err2event(c, b, "Unable to prepare"))?;
+    let rows = s
+        .query_map(params![after, limit], row2bytes)
+        .map_err(bucket_err2event(c, b, "Unable to get keys"))?;
+    rows.map(|r| r.map_err(err2event("Unable to get a row")))
+        .collect()
+}
+
+/// Counts number of rows in a bucket.
+pub fn count<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            SELECT COUNT(*) FROM {}
+        "#,
+        name,
+    );
+    let cnt: i64 = c
+        .query_row(query.as_str(), [], |r: &Row| r.get(0))
+        .map_err(bucket_err2event(c, b, "Unable to get table count"))?;
+    u64::try_from(cnt).map_err(|e| Event::UnexpectedError(format!("Count out of range: {}", e)))
+}
+
+/// Drops a bucket if exists(returns 1 if dropped).
+pub fn drop_bucket<C>(c: &mut C, b: &Bucket) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let exists: bool = chk(c, b)?;
+    let query: String = format!(
+        r#"
+            DROP TABLE IF EXISTS {}
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), [])
+        .map_err(err2event("Unable to drop a bucket"))
+        .map(|_| u64::from(exists))
+}
+
+/// Deletes a row from a bucket.
+pub fn delete<C>(c: &mut C, b: &Bucket, key: &[u8]) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            DELETE FROM {}
+            WHERE key=?1
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), params![key])
+        .map_err(bucket_err2event(c, b, "Unable to delete a row"))
+        .map(|cnt| cnt as u64)
+}
+
+/// Deletes rows in the key range(lower: inclusive, upper: exclusive).
+pub fn delete_range<C>(c: &mut C, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event>
+where
+    C: Deref<Target = Connection>,
+{
+    let name: &str = b.as_safe_identifier()?;
+    let query: String = format!(
+        r#"
+            DELETE FROM {}
+            WHERE ?1 <= key AND key < ?2
+        "#,
+        name,
+    );
+    c.execute(query.as_str(), params![lower, upper])
+        .map_err(bucket_err2event(c, b, "Unable to delete rows"))
+        .map(|cnt| cnt as u64)
+}
+
+/// Commits changes.
+pub fn commit(t: Transaction) -> Result<(), Event> {
+    t.commit().map_err(err2event("Unable to commit changes"))
+}
+
+/// Savepoint name used by `Transactional`.
+const SAVEPOINT: &str = "kv2spacetimedb";
+
+/// Uses a savepoint(nested transactions use nested savepoints).
+impl Transactional for Transaction<'_> {
+    fn begin(&mut self) -> Result<(), Event> {
+        let query: String = format!("SAVEPOINT {}", SAVEPOINT);
+        self.execute_batch(query.as_str())
+            .map_err(err2event("Unable to create a savepoint"))
+    }
+
+    fn commit(&mut self) -> Result<(), Event> {
+        let query: String = format!("RELEASE SAVEPOINT {}", SAVEPOINT);
+        self.execute_batch(query.as_str())
+            .map_err(err2event("Unable to release a savepoint"))
+    }
+
+    fn rollback(&mut self) -> Result<(), Event> {
+        let query: String = format!(
+            "ROLLBACK TO SAVEPOINT {0}; RELEASE SAVEPOINT {0}",
+            SAVEPOINT
+        );
+        self.execute_batch(query.as_str())
+            .map_err(err2event("Unable to rollback to a savepoint"))
+    }
+}
+
+impl Create for Transaction<'_> {
+    fn create(&mut self, b: &Bucket) -> Result<u64, Event> {
+        create(self, b)
+    }
+}
+
+impl UpsertRaw for Transaction<'_> {
+    fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
+        upsert(self, b, i)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        commit(self)
+    }
+}
+
+impl BatchUpsert for Transaction<'_> {
+    fn upsert_batch(&mut self, b: &Bucket, items: &[RawItem]) -> Result<u64, Event> {
+        upsert_batch(self, b, items)
+    }
+}
+
+impl ConflictUpsert for Transaction<'_> {
+    fn upsert_with_policy(
+        &mut self,
+        b: &Bucket,
+        i: &RawItem,
+        policy: &ConflictPolicy,
+    ) -> Result<u64, Event> {
+        match policy {
+            ConflictPolicy::Overwrite => upsert(self, b, i),
+            ConflictPolicy::KeepExisting => insert_or_keep(self, b, i),
+            _ => conflict::upsert_resolved(self, b, i, policy),
+        }
+    }
+}
+
+impl GetRaw for Transaction<'_> {
+    fn get(&mut self, b: &Bucket, key: &[u8]) -> Result<Option<Vec<u8>>, Event> {
+        get(self, b, key)
+    }
+
+    fn chk(&mut self, b: &Bucket) -> Result<bool, Event> {
+        chk(self, b)
+    }
+}
+
+impl ListBuckets for Transaction<'_> {
+    fn list(&mut self) -> Result<Vec<Bucket>, Event> {
+        list(self)
+    }
+}
+
+impl ListKeys<Vec<u8>> for Transaction<'_> {
+    fn list(&mut self, b: &Bucket) -> Result<Vec<Vec<u8>>, Event> {
+        list_keys(self, b)
+    }
+}
+
+impl ListKeysAfter for Transaction<'_> {
+    fn list_after(
+        &mut self,
+        b: &Bucket,
+        after: Option<&[u8]>,
+        limit: u32,
+    ) -> Result<Vec<Vec<u8>>, Event> {
+        list_keys_after(self, b, after, limit)
+    }
+}
+
+impl Counter for Transaction<'_> {
+    fn count(&mut self, b: &Bucket) -> Result<Count, Event> {
+        let cnt: u64 = count(self, b)?;
+        let dt: DateTime = DateTime::time_source_new_std()()?;
+        Ok(Count::new(cnt, dt))
+    }
+}
+
+impl DropBucket for Transaction<'_> {
+    fn drop(&mut self, b: &Bucket) -> Result<u64, Event> {
+        drop_bucket(self, b)
+    }
+}
+
+impl DeleteRow for Transaction<'_> {
+    fn delete(&mut self, b: &Bucket, key: &[u8]) -> Result<u64, Event> {
+        delete(self, b, key)
+    }
+
+    fn finalize(self) -> Result<(), Event> {
+        commit(self)
+    }
+}
+
+impl DeleteRange for Transaction<'_> {
+    fn delete_range(&mut self, b: &Bucket, lower: &[u8], upper: &[u8]) -> Result<u64, Event> {
+        delete_range(self, b, lower, upper)
+    }
+}
+
+#[cfg(test)]
+mod test_sqlite {
+
+    mod upsert_all_shared {
+        use rusqlite::Connection;
+
+        use crate::item::Item;
+        use crate::kvstore::{sqlite, upsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_upsert() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let t = c.transaction().unwrap();
+            let cnt: u64 = upsert::upsert_all_shared(
+                sqlite::upsert,
+                sqlite::create,
+                t,
+                sqlite::commit,
+                source.into_iter(),
+            )
+            .unwrap();
+            assert_eq!(cnt, 9);
+
+            let buckets: Vec<Bucket> = sqlite::list(&mut &c).unwrap();
+            assert_eq!(buckets.len(), 7);
+
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ));
+            let val: Option<Vec<u8>> = sqlite::get(&mut &c, &b, b"00:30:21.0Z").unwrap();
+            assert_eq!(val, Some(b"42".to_vec()));
+            assert_eq!(
+                sqlite::count(&mut &c, &Bucket::new_devices_master()).unwrap(),
+                2
+            );
+        }
+    }
+
+    mod upsert_batch {
+        use rusqlite::Connection;
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::{sqlite, upsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_chunks() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::from(String::from("batch"));
+            sqlite::create(&mut &c, &b).unwrap();
+            let mut items: Vec<RawItem> = (0..1000u32)
+                .map(|n: u32| Item::new(n.to_be_bytes().to_vec(), b"old".to_vec()))
+                .collect();
+            items.push(Item::new(0u32.to_be_bytes().to_vec(), b"new".to_vec()));
+            let cnt: u64 = sqlite::upsert_batch(&mut &c, &b, &items).unwrap();
+            assert_eq!(cnt, 1000);
+            assert_eq!(sqlite::count(&mut &c, &b).unwrap(), 1000);
+            let val: Option<Vec<u8>> = sqlite::get(&mut &c, &b, &0u32.to_be_bytes()).unwrap();
+            assert_eq!(val, Some(b"new".to_vec()));
+
+            // unchanged values are not updated(only the first key: new -> old)
+            let cnt: u64 = sqlite::upsert_batch(&mut &c, &b, &items[..10]).unwrap();
+            assert_eq!(cnt, 1);
+        }
+
+        #[test]
+        fn test_upsert_all() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("dafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let t = c.transaction().unwrap();
+            let cnt: u64 = upsert::upsert_all_shared_batch_ex(
+                sqlite::upsert_batch,
+                sqlite::create,
+                t,
+                sqlite::commit,
+                source.into_iter(),
+                upsert::upsert_value_generator_new_func_default(),
+            )
+            .unwrap();
+            assert_eq!(cnt, 9);
+            assert_eq!(
+                sqlite::count(&mut &c, &Bucket::new_devices_master()).unwrap(),
+                2
+            );
+        }
+    }
+
+    mod upsert_with_policy {
+        use rusqlite::{Connection, Transaction};
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::conflict::{ConflictPolicy, ConflictUpsert};
+        use crate::kvstore::sqlite;
+        use crate::{bucket::Bucket, datetime::DateTime};
+
+        #[test]
+        fn test_policies() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::from(String::from("data"));
+            sqlite::create(&mut t, &b).unwrap();
+            let i = |v: &str| -> RawItem { Item::new(b"k".to_vec(), v.into()) };
+
+            let keep = ConflictPolicy::KeepExisting;
+            assert_eq!(
+                t.upsert_with_policy(&b, &i("2022-11-02T00:30:21Z"), &keep)
+                    .unwrap(),
+                1
+            );
+            assert_eq!(
+                t.upsert_with_policy(&b, &i("2022-11-02T00:30:19Z"), &keep)
+                    .unwrap(),
+                0
+            );
+
+            let newer = ConflictPolicy::NewerByDateTime(Box::new(|v: &[u8]| {
+                DateTime::parse_rfc3339(std::str::from_utf8(v).ok()?).ok()
+            }));
+            assert_eq!(
+                t.upsert_with_policy(&b, &i("2022-11-02T00:30:20Z"), &newer)
+                    .unwrap(),
+                0
+            );
+            assert_eq!(
+                t.upsert_with_policy(&b, &i("2022-11-02T00:30:22Z"), &newer)
+                    .unwrap(),
+                1
+            );
+
+            let overwrite = ConflictPolicy::Overwrite;
+            assert_eq!(
+                t.upsert_with_policy(&b, &i("2022-11-02T00:30:00Z"), &overwrite)
+                    .unwrap(),
+                1
+            );
+            let val: Option<Vec<u8>> = sqlite::get(&mut t, &b, b"k").unwrap();
+            assert_eq!(val, Some(b"2022-11-02T00:30:00Z".to_vec()));
+        }
+    }
+
+    mod delete_stale_data_default_func {
+        use rusqlite::Connection;
+
+        use crate::item::Item;
+        use crate::kvstore::{delete, sqlite, upsert};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_delete() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_01".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let t = c.transaction().unwrap();
+            upsert::upsert_all_shared(
+                sqlite::upsert,
+                sqlite::create,
+                t,
+                sqlite::commit,
+                source.into_iter(),
+            )
+            .unwrap();
+
+            let t = c.transaction().unwrap();
+            let cnt: u64 = delete::delete_stale_data_default_func(
+                sqlite::drop_bucket,
+                sqlite::delete,
+                sqlite::list,
+                t,
+                sqlite::commit,
+                Date::new_unchecked("2022_11_02".into()),
+            )
+            .unwrap();
+
+            // 2 buckets dropped(data, devices master), 2 rows deleted(dates masters).
+            assert_eq!(cnt, 4);
+
+            let buckets: Vec<Bucket> = sqlite::list(&mut &c).unwrap();
+            assert_eq!(buckets.len(), 5);
+            let stale: Bucket = Bucket::from(String::from(
+                "data_2022_11_01_cafef00ddeadbeafface864299792458",
+            ));
+            assert!(!sqlite::chk(&mut &c, &stale).unwrap());
+        }
+    }
+
+    mod count_data_bucket4date_new {
+        use rusqlite::Connection;
+
+        use crate::datetime::DateTime;
+        use crate::item::Item;
+        use crate::kvstore::count::Counter;
+        use crate::kvstore::{count, sqlite, upsert};
+        use crate::{bucket::Bucket, count::Count, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_count() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let source = vec![
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                ),
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked("2022_11_02".into()),
+                    Item::new(b"00:30:22.0Z".to_vec(), b"634".to_vec()),
+                ),
+            ];
+            let mut t = c.transaction().unwrap();
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut t, b, i);
+            upsert::upsert_all(source.into_iter(), &mut cu).unwrap();
+            sqlite::commit(t).unwrap();
+
+            let counter = |b: &Bucket| sqlite::count(&mut &c, b);
+            let time_source = || Ok(DateTime::from_unixtime_us(0));
+            let mut f = count::count_data_bucket4date_new(counter, time_source);
+            let cnt: Count = f(
+                &Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                &Date::new_unchecked("2022_11_02".into()),
+            )
+            .unwrap();
+            assert_eq!(cnt.as_count(), 2);
+            drop(f);
+
+            let mut t = c.transaction().unwrap();
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_11_02_cafef00ddeadbeafface864299792458",
+            ));
+            assert_eq!(Counter::count(&mut t, &b).unwrap().as_count(), 2);
+        }
+    }
+    mod unsafe_identifier {
+        use rusqlite::Connection;
+
+        use crate::item::Item;
+        use crate::kvstore::sqlite;
+        use crate::{bucket::Bucket, evt::Event};
+
+        #[test]
+        fn test_reject() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::from(String::from("dates(key BLOB); DROP TABLE dates; --"));
+            let r: Result<_, _> = sqlite::create(&mut &c, &b);
+            assert!(matches!(r, Err(Event::UnsafeIdentifier(_))));
+            let r: Result<_, _> = sqlite::upsert(&mut &c, &b, &Item::new(vec![], vec![]));
+            assert!(matches!(r, Err(Event::UnsafeIdentifier(_))));
+            let r: Result<_, _> = sqlite::drop_bucket(&mut &c, &b);
+            assert!(matches!(r, Err(Event::UnsafeIdentifier(_))));
+        }
+    }
+    mod missing_bucket {
+        use rusqlite::Connection;
+
+        use crate::item::Item;
+        use crate::kvstore::sqlite;
+        use crate::{bucket::Bucket, evt::Event};
+
+        #[test]
+        fn test_not_found() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            let r: Result<_, _> = sqlite::count(&mut &c, &b);
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            let r: Result<_, _> = sqlite::get(&mut &c, &b, b"2022_11_02");
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            let r: Result<_, _> = sqlite::list_keys(&mut &c, &b);
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            let r: Result<_, _> = sqlite::upsert(&mut &c, &b, &Item::new(vec![], vec![]));
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            let r: Result<_, _> = sqlite::delete(&mut &c, &b, b"2022_11_02");
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+        }
+
+        #[test]
+        fn test_other_error() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            sqlite::create(&mut &c, &b).unwrap();
+            c.execute_batch("ALTER TABLE dates RENAME COLUMN val TO v")
+                .unwrap();
+            let r: Result<_, _> = sqlite::get(&mut &c, &b, b"2022_11_02");
+            assert!(matches!(r, Err(Event::BackendError { .. })));
+        }
+    }
+    mod delete_range {
+        use rusqlite::Connection;
+
+        use crate::bucket::Bucket;
+        use crate::item::Item;
+        use crate::kvstore::sqlite;
+
+        #[test]
+        fn test_delete() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            sqlite::create(&mut &c, &b).unwrap();
+            for d in ["2022_11_01", "2022_11_02", "2022_11_03"] {
+                let i = Item::new(d.as_bytes().to_vec(), vec![]);
+                sqlite::upsert(&mut &c, &b, &i).unwrap();
+            }
+            let cnt: u64 = sqlite::delete_range(&mut &c, &b, &[], b"2022_11_03").unwrap();
+            assert_eq!(cnt, 2);
+            let keys: Vec<Vec<u8>> = sqlite::list_keys(&mut &c, &b).unwrap();
+            assert_eq!(keys, vec![b"2022_11_03".to_vec()]);
+        }
+    }
+
+    mod list_keys_after {
+        use rusqlite::Connection;
+
+        use crate::bucket::Bucket;
+        use crate::item::Item;
+        use crate::kvstore::sqlite;
+
+        #[test]
+        fn test_pages() {
+            let c: Connection = Connection::open_in_memory().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            sqlite::create(&mut &c, &b).unwrap();
+            for d in ["2022_11_01", "2022_11_02", "2022_11_03"] {
+                let i = Item::new(d.as_bytes().to_vec(), vec![]);
+                sqlite::upsert(&mut &c, &b, &i).unwrap();
+            }
+            let first: Vec<Vec<u8>> = sqlite::list_keys_after(&mut &c, &b, None, 2).unwrap();
+            assert_eq!(first, vec![b"2022_11_01".to_vec(), b"2022_11_02".to_vec()]);
+            let last: Vec<Vec<u8>> =
+                sqlite::list_keys_after(&mut &c, &b, Some(b"2022_11_02"), 2).unwrap();
+            assert_eq!(last, vec![b"2022_11_03".to_vec()]);
+            let empty: Vec<Vec<u8>> =
+                sqlite::list_keys_after(&mut &c, &b, Some(b"2022_11_03"), 2).unwrap();
+            assert!(empty.is_empty());
+        }
+    }
+
+    mod transactional {
+        use rusqlite::{Connection, Transaction};
+
+        use crate::bucket::Bucket;
+        use crate::evt::Event;
+        use crate::item::Item;
+        use crate::kvstore::sqlite;
+        use crate::kvstore::transaction::{self, Transactional};
+
+        #[test]
+        fn test_rollback() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let mut t: Transaction = c.transaction().unwrap();
+            let b: Bucket = Bucket::new_dates_master();
+            sqlite::create(&mut t, &b).unwrap();
+            let kept = Item::new(b"2022_11_01".to_vec(), vec![]);
+            sqlite::upsert(&mut t, &b, &kept).unwrap();
+
+            let r: Result<u64, Event> = transaction::transaction(&mut t, |t: &mut Transaction| {
+                sqlite::upsert(t, &b, &Item::new(b"2022_11_02".to_vec(), vec![]))?;
+                let missing: Bucket = Bucket::new_devices_master();
+                sqlite::upsert(t, &missing, &Item::new(b"dev".to_vec(), vec![]))
+            });
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            let keys: Vec<Vec<u8>> = sqlite::list_keys(&mut t, &b).unwrap();
+            assert_eq!(keys, vec![b"2022_11_01".to_vec()]);
+
+            let cnt: u64 = transaction::transaction(&mut t, |t: &mut Transaction| {
+                sqlite::upsert(t, &b, &Item::new(b"2022_11_03".to_vec(), vec![]))
+            })
+            .unwrap();
+            assert_eq!(cnt, 1);
+            assert!(Transactional::commit(&mut t).is_err());
+            sqlite::commit(t).unwrap();
+            assert_eq!(sqlite::count(&mut &c, &b).unwrap(), 2);
+        }
+    }
+
+    mod with_namer {
+        use rusqlite::{Connection, Transaction};
+
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::{delete, get, get::GetRaw, sqlite, upsert};
+        use crate::namer::{BucketNamer, SimpleBucketNamer};
+        use crate::{bucket::Bucket, data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_prefixed() {
+            let mut c: Connection = Connection::open_in_memory().unwrap();
+            let namer = SimpleBucketNamer::default()
+                .with_prefix("telemetry_".into())
+                .unwrap()
+                .with_device_first(true);
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date = Date::new_unchecked("2022_11_02".into());
+            let source = vec![Data::new(
+                dev.clone(),
+                date.clone(),
+                Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+            )];
+            let mut t: Transaction = c.transaction().unwrap();
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut t, b, i);
+            upsert::upsert_all_with_namer(
+                source.into_iter(),
+                &mut cu,
+                upsert::upsert_value_generator_new_func_default(),
+                &namer,
+            )
+            .unwrap();
+
+            let mut g = |b: &Bucket, k: &[u8]| GetRaw::get(&mut t, b, k);
+            let found: Option<RawItem> =
+                get::get_raw_with_namer(&mut g, &dev, &date, b"00:30:21.0Z", &namer).unwrap();
+            assert_eq!(found.map(|i| i.as_val().to_vec()), Some(b"42".to_vec()));
+            assert_eq!(sqlite::count(&mut t, &namer.devices()).unwrap(), 1);
+
+            let cnt: u64 = delete::delete_device_with_namer(t, dev, &namer).unwrap();
+
+            // 2 buckets dropped, 2 rows deleted(devices, devices4date).
+            assert_eq!(cnt, 4);
+            let buckets: Vec<Bucket> = sqlite::list(&mut &c).unwrap();
+            assert_eq!(buckets.len(), 3);
+            assert!(buckets.contains(&namer.dates()));
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Runs operations in a transaction which rolls back on error.
+
+use crate::item::RawItem;
+use crate::{bucket::Bucket, data::RawData, date::Date, device::Device, evt::Event};
+
+use crate::kvstore::create::Create;
+use crate::kvstore::delete::{self, DeleteRow, DropBucket};
+use crate::kvstore::facade::StoreRef;
+use crate::kvstore::list::ListBuckets;
+use crate::kvstore::upsert::{self, UpsertRaw};
+
+/// Begins/commits/rolls back a transaction.
+pub trait Transactional {
+    /// Starts a transaction.
+    fn begin(&mut self) -> Result<(), Event>;
+
+    /// Saves changes made after `begin`.
+    fn commit(&mut self) -> Result<(), Event>;
+
+    /// Discards changes made after `begin`.
+    fn rollback(&mut self) -> Result<(), Event>;
+}
+
+/// Runs operations in a transaction.
+///
+/// - Commits if the operations succeeded.
+/// - Rolls back if the operations(or the commit) failed and returns the error.
+/// - Returns non-retriable `BackendError` if the rollback failed.
+///
+/// # Arguments
+/// - store: Begins/commits/rolls back a transaction.
+/// - f: Operations using the store.
+pub fn transaction<S, T, F>(store: &mut S, f: F) -> Result<T, Event>
+where
+    S: Transactional,
+    F: FnOnce(&mut S) -> Result<T, Event>,
+{
+    store.begin()?;
+    let result: Result<T, Event> = f(store).and_then(|t: T| store.commit().map(|_| t));
+    match result {
+        Ok(t) => Ok(t),
+        Err(e) => match store.rollback() {
+            Ok(_) => Err(e),
+            Err(r) => {
+                let msg: String = format!("Unable to rollback after error({}): {}", e, r);
+                Err(Event::backend(msg, false, r))
+            }
+        },
+    }
+}
+
+/// Saves data got from source in a transaction.
+///
+/// Nothing will be saved if any upsert failed.
+///
+/// # Arguments
+/// - store: Creates buckets, Upserts items.
+/// - source: `RawData` source iterator.
+pub fn upsert_all<S, I>(store: &mut S, source: I) -> Result<u64, Event>
+where
+    S: Transactional + Create + UpsertRaw,
+    I: Iterator<Item = RawData>,
+{
+    transaction(store, |s: &mut S| {
+        let mut cu = |b: &Bucket, i: &RawItem| upsert::create_upsert(s, b, i);
+        upsert::upsert_all(source, &mut cu)
+    })
+}
+
+/// Removes a device in a transaction.
+///
+/// # Arguments
+/// - store: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - target: The device to be removed.
+pub fn delete_device<S>(store: &mut S, target: Device) -> Result<u64, Event>
+where
+    S: Transactional + DropBucket + DeleteRow + ListBuckets,
+{
+    transaction(store, |s: &mut S| {
+        delete::delete_device_default(StoreRef::new(s), target)
+    })
+}
+
+/// Drops stale buckets and deletes stale rows in a transaction.
+///
+/// # Arguments
+/// - store: Gets list of buckets, Drops a bucket, Deletes a row.
+/// - lbi: Date threshold(fresh date lower bound, inclusive).
+pub fn delete_stale_data<S>(store: &mut S, lbi: Date) -> Result<u64, Event>
+where
+    S: Transactional + DropBucket + DeleteRow + ListBuckets,
+{
+    transaction(store, |s: &mut S| {
+        delete::delete_stale_data_default(StoreRef::new(s), lbi)
+    })
+}
+
+#[cfg(test)]
+mod test_transaction {
+
+    mod transaction {
+        use crate::bucket::Bucket;
+        use crate::evt::Event;
+        use crate::item::Item;
+        use crate::kvstore::create::Create;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::transaction::{self, Transactional};
+        use crate::kvstore::upsert::UpsertRaw;
+
+        #[test]
+        fn test_commit() {
+            let mut m: MemStore = MemStore::new();
+            let b: Bucket = Bucket::from(String::from("data"));
+            let cnt: u64 = transaction::transaction(&mut m, |s: &mut MemStore| {
+                Create::create(s, &b)?;
+                UpsertRaw::upsert(s, &b, &Item::new(b"k".to_vec(), b"v".to_vec()))
+            })
+            .unwrap();
+            assert_eq!(cnt, 1);
+            assert!(!m.in_transaction());
+            assert_eq!(m.count(&b).unwrap(), 1);
+        }
+
+        #[test]
+        fn test_rollback() {
+            let mut m: MemStore = MemStore::new();
+            let b: Bucket = Bucket::from(String::from("data"));
+            Create::create(&mut m, &b).unwrap();
+            let before: MemStore = m.clone();
+
+            let r: Result<u64, Event> = transaction::transaction(&mut m, |s: &mut MemStore| {
+                UpsertRaw::upsert(s, &b, &Item::new(b"k".to_vec(), b"v".to_vec()))?;
+                let missing: Bucket = Bucket::from(String::from("missing"));
+                UpsertRaw::upsert(s, &missing, &Item::new(b"k".to_vec(), b"v".to_vec()))
+            });
+            assert!(matches!(r, Err(Event::BucketNotFound(_))));
+            assert_eq!(m, before);
+        }
+
+        #[test]
+        fn test_rollback_error() {
+            let mut m: MemStore = MemStore::new();
+            let r: Result<(), Event> = transaction::transaction(&mut m, |s: &mut MemStore| {
+                s.rollback()?;
+                Err(Event::Timeout("upsert".into()))
+            });
+            let e: Event = r.unwrap_err();
+            assert!(matches!(e, Event::BackendError { .. }));
+            assert!(!e.is_retriable());
+        }
+
+        #[test]
+        fn test_nested() {
+            let mut m: MemStore = MemStore::new();
+            m.begin().unwrap();
+            assert!(m.begin().is_err());
+            m.commit().unwrap();
+            assert!(m.commit().is_err());
+            assert!(m.rollback().is_err());
+        }
+
+        #[test]
+        fn test_snapshot_hidden() {
+            let mut m: MemStore = MemStore::new();
+            Create::create(&mut m, &Bucket::from(String::from("data"))).unwrap();
+            m.begin().unwrap();
+            let cloned: MemStore = m.clone();
+            assert!(!cloned.in_transaction());
+            assert_eq!(cloned, m);
+            assert!(!format!("{:?}", m).contains("snapshot"));
+            m.commit().unwrap();
+        }
+    }
+
+    mod upsert_all {
+        use crate::evt::Event;
+        use crate::item::Item;
+        use crate::kvstore::delete::DropBucket;
+        use crate::kvstore::facade::SpacetimeDb;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::transaction;
+        use crate::{bucket::Bucket, data::Data, data::RawData, date::Date, device::Device};
+
+        const DEV: &str = "cafef00ddeadbeafface864299792458";
+
+        fn source() -> impl Iterator<Item = RawData> {
+            ["2022_11_01", "2022_11_02"].into_iter().map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked(DEV.into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            })
+        }
+
+        #[test]
+        fn test_upsert_delete() {
+            let mut m: MemStore = MemStore::new();
+            assert!(0 < transaction::upsert_all(&mut m, source()).unwrap());
+            let lbi: Date = Date::new_unchecked("2022_11_02".into());
+            assert!(0 < transaction::delete_stale_data(&mut m, lbi).unwrap());
+            let dev: Device = Device::new_unchecked(DEV.into());
+            assert!(0 < transaction::delete_device(&mut m, dev).unwrap());
+            assert!(!m.in_transaction());
+        }
+
+        #[test]
+        fn test_facade_rollback() {
+            let mut db: SpacetimeDb<MemStore> = SpacetimeDb::new(MemStore::new());
+            db.upsert(source()).unwrap();
+            let before: MemStore = db.as_store_mut().clone();
+
+            let r: Result<u64, Event> = db.transaction(|db| {
+                let lbi: Date = Date::new_unchecked("2022_11_02".into());
+                db.remove_stale(lbi)?;
+                let b: Bucket = Bucket::from(String::from("missing"));
+                DropBucket::drop(db.as_store_mut(), &b)?;
+                Err(Event::Timeout("remove".into()))
+            });
+            assert!(matches!(r, Err(Event::Timeout(_))));
+            assert_eq!(db.into_inner(), before);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
 use std::ops::DerefMut;
 use std::sync::Mutex;
 
 use crate::item::{Item, RawItem};
+use crate::namer::{BucketNamer, SimpleBucketNamer};
 use crate::{bucket::Bucket, data::RawData, date::Date, device::Device, evt::Event};
 
 use crate::kvstore::create::Create;
@@ -23,6 +25,43 @@ pub trait IntoShared<S> {
     fn into_inner(self) -> Result<S, Event>;
 }
 
+/// Upserts items into a bucket at once(e.g, multi-row INSERT).
+///
+/// The default implementation upserts items one by one using `UpsertRaw`.
+///
+/// There is no blanket impl for `UpsertRaw` types: without specialization(not in stable Rust),
+/// a blanket impl would prevent backends from overriding `upsert_batch`.
+/// Each backend opts in(`impl BatchUpsert for X {}` to use the default).
+/// For the same reason, batch upserts are separate functions(e.g, `upsert_all_batch_ex`)
+/// instead of being chosen by the row-by-row functions(e.g, `upsert_all_ex`).
+pub trait BatchUpsert: UpsertRaw {
+    /// Upserts items into a bucket.
+    ///
+    /// Items with the same key may be deduplicated(the last one wins).
+    fn upsert_batch(&mut self, b: &Bucket, items: &[RawItem]) -> Result<u64, Event> {
+        items
+            .iter()
+            .try_fold(0, |tot, item| self.upsert(b, item).map(|cnt| cnt + tot))
+    }
+}
+
+/// Removes items which have the same key(the last one wins, sorted by key).
+pub fn dedup_by_key(items: &[RawItem]) -> Vec<&RawItem> {
+    let m: BTreeMap<&[u8], &RawItem> = items
+        .iter()
+        .map(|i: &RawItem| (i.as_key().as_slice(), i))
+        .collect();
+    m.into_values().collect()
+}
+
+/// Creates new batch upsert using `BatchUpsert`.
+pub fn upsert_batch_new_func<U>(mut u: U) -> impl FnMut(&Bucket, &[RawItem]) -> Result<u64, Event>
+where
+    U: BatchUpsert,
+{
+    move |b: &Bucket, items: &[RawItem]| u.upsert_batch(b, items)
+}
+
 /// Creates new upsert using `UpsertRaw`.
 pub fn upsert_raw_new_func<U>(mut u: U) -> impl FnMut(&Bucket, &RawItem) -> Result<u64, Event>
 where
@@ -67,6 +106,8 @@ where
 
 /// Upserts data which creates a bucket before upsert.
 ///
+/// Items are upserted one by one; use `upsert_all_shared_batch_ex` for `BatchUpsert` backends.
+///
 /// # Arguments
 /// - upsert: Upserts an item into a bucket using shared resource.
 /// - create: Creates a bucket using shared resource.
@@ -89,15 +130,127 @@ where
     I: Iterator<Item = RawData>,
     G: UpsertValueGenerator,
 {
-    let mut upsert_raw = UpsertAfterCreateShared {
+    upsert_all_shared_with_policy(
         upsert,
         create,
         shared,
         finalize,
+        requests,
+        upsert_value_gen,
+        MasterUpsertPolicy::Every,
+    )
+}
+
+/// Upserts data which creates a bucket before upsert using the policy to upsert master rows.
+///
+/// # Arguments
+/// - upsert: Upserts an item into a bucket using shared resource.
+/// - create: Creates a bucket using shared resource.
+/// - shared: Vendor specific shared resource for upsert/create.
+/// - finalize: Finalizes the shared resource.
+/// - requests: Data to be upserted.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - policy: Decides how master rows are upserted.
+pub fn upsert_all_shared_with_policy<U, C, T, F, I, G>(
+    upsert: U,
+    create: C,
+    shared: T,
+    finalize: F,
+    requests: I,
+    upsert_value_gen: G,
+    policy: MasterUpsertPolicy,
+) -> Result<u64, Event>
+where
+    U: Fn(&mut T, &Bucket, &RawItem) -> Result<u64, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event>,
+    F: Fn(T) -> Result<(), Event>,
+    I: Iterator<Item = RawData>,
+    G: UpsertValueGenerator,
+{
+    let upsert_batch = |t: &mut T, b: &Bucket, items: &[RawItem]| {
+        items
+            .iter()
+            .try_fold(0, |tot, item| upsert(t, b, item).map(|cnt| cnt + tot))
     };
-    let mut upst = |b: &Bucket, i: &RawItem| create_upsert(&mut upsert_raw, b, i);
-    let cnt: u64 = upsert_all_ex(requests, &mut upst, upsert_value_gen)?;
-    upsert_raw.finalize()?;
+    upsert_all_shared_batch_with_policy(
+        upsert_batch,
+        create,
+        shared,
+        finalize,
+        requests,
+        upsert_value_gen,
+        policy,
+    )
+}
+
+/// Upserts data per bucket which creates a bucket before upserts.
+///
+/// # Arguments
+/// - upsert_batch: Upserts items into a bucket using shared resource.
+/// - create: Creates a bucket using shared resource.
+/// - shared: Vendor specific shared resource for upsert/create.
+/// - finalize: Finalizes the shared resource.
+/// - requests: Data to be upserted.
+/// - upsert_value_gen: Value generator for master buckets.
+pub fn upsert_all_shared_batch_ex<B, C, T, F, I, G>(
+    upsert_batch: B,
+    create: C,
+    shared: T,
+    finalize: F,
+    requests: I,
+    upsert_value_gen: G,
+) -> Result<u64, Event>
+where
+    B: Fn(&mut T, &Bucket, &[RawItem]) -> Result<u64, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event>,
+    F: Fn(T) -> Result<(), Event>,
+    I: Iterator<Item = RawData>,
+    G: UpsertValueGenerator,
+{
+    upsert_all_shared_batch_with_policy(
+        upsert_batch,
+        create,
+        shared,
+        finalize,
+        requests,
+        upsert_value_gen,
+        MasterUpsertPolicy::Every,
+    )
+}
+
+/// Upserts data per bucket which creates a bucket before upserts using the policy.
+///
+/// # Arguments
+/// - upsert_batch: Upserts items into a bucket using shared resource.
+/// - create: Creates a bucket using shared resource.
+/// - shared: Vendor specific shared resource for upsert/create.
+/// - finalize: Finalizes the shared resource.
+/// - requests: Data to be upserted.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - policy: Decides how master rows are upserted.
+pub fn upsert_all_shared_batch_with_policy<B, C, T, F, I, G>(
+    upsert_batch: B,
+    create: C,
+    mut shared: T,
+    finalize: F,
+    requests: I,
+    upsert_value_gen: G,
+    policy: MasterUpsertPolicy,
+) -> Result<u64, Event>
+where
+    B: Fn(&mut T, &Bucket, &[RawItem]) -> Result<u64, Event>,
+    C: Fn(&mut T, &Bucket) -> Result<u64, Event>,
+    F: Fn(T) -> Result<(), Event>,
+    I: Iterator<Item = RawData>,
+    G: UpsertValueGenerator,
+{
+    let mut upst = |b: &Bucket, items: &[RawItem]| {
+        let cnt_c: u64 = create(&mut shared, b)?;
+        let cnt_u: u64 = upsert_batch(&mut shared, b, items)?;
+        Ok(cnt_c + cnt_u)
+    };
+    let cnt: u64 = upsert_all_batch_ex_with_policy(requests, &mut upst, upsert_value_gen, policy)?;
+    finalize(shared)?;
     Ok(cnt)
 }
 
@@ -162,6 +315,31 @@ where
     }
 }
 
+/// Decides how master rows are upserted within a batch(single call of an upsert function).
+///
+/// Master rows are identified by (master bucket, date/device).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MasterUpsertPolicy {
+    /// Upserts master rows for every `RawData`(duplicated items will be ignored).
+    #[default]
+    Every,
+
+    /// Generates and upserts a master row only once.
+    ///
+    /// The value generator is called once per master row.
+    OncePerBatch,
+
+    /// Upserts a master row once using the first generated value.
+    ///
+    /// The value generator is called for every `RawData`(e.g, for side effects).
+    FirstSeen,
+
+    /// Upserts a master row once using the last generated value.
+    ///
+    /// The value generator is called for every `RawData`.
+    LastSeen,
+}
+
 struct UpsertRequest<K, V> {
     bucket: Bucket,
     item: Item<K, V>,
@@ -192,6 +370,25 @@ pub trait UpsertValueGenerator {
     fn devices4date(&self, d: &Device) -> RawItem;
 }
 
+impl<G> UpsertValueGenerator for &G
+where
+    G: UpsertValueGenerator,
+{
+    fn devices(&self, d: &Device) -> RawItem {
+        (*self).devices(d)
+    }
+    fn dates(&self, d: &Date) -> RawItem {
+        (*self).dates(d)
+    }
+
+    fn dates4device(&self, d: &Date) -> RawItem {
+        (*self).dates4device(d)
+    }
+    fn devices4date(&self, d: &Device) -> RawItem {
+        (*self).devices4date(d)
+    }
+}
+
 /// Creates new value generator using closures.
 ///
 /// # Arguments
@@ -260,9 +457,10 @@ where
 }
 
 impl UpsertRequest<Vec<u8>, Vec<u8>> {
-    fn from_data<G>(d: RawData, upsert_value_gen: &G) -> Vec<Self>
+    fn from_data<G, N>(d: RawData, upsert_value_gen: &G, namer: &N) -> Vec<Self>
     where
         G: UpsertValueGenerator,
+        N: BucketNamer,
     {
         let dev: &Device = d.as_device();
         let date: &Date = d.as_date();
@@ -272,11 +470,11 @@ impl UpsertRequest<Vec<u8>, Vec<u8>> {
         let i_dates: RawItem = upsert_value_gen.dates(date);
         let i_devices: RawItem = upsert_value_gen.devices(dev);
 
-        let b_data: Bucket = Bucket::new_data_bucket(dev, date);
-        let b_dates4device: Bucket = Bucket::new_dates_master_for_device(dev);
-        let b_devices4date: Bucket = Bucket::new_devices_master_for_date(date);
-        let b_dates: Bucket = Bucket::new_dates_master();
-        let b_devices: Bucket = Bucket::new_devices_master();
+        let b_data: Bucket = namer.data(dev, date);
+        let b_dates4device: Bucket = namer.dates4device(dev);
+        let b_devices4date: Bucket = namer.devices4date(date);
+        let b_dates: Bucket = namer.dates();
+        let b_devices: Bucket = namer.devices();
 
         let item: RawItem = d.into_item();
 
@@ -291,13 +489,18 @@ impl UpsertRequest<Vec<u8>, Vec<u8>> {
         ]
     }
 
-    fn bulkdata2map<I, G>(bulk: I, upsert_value_gen: G) -> BTreeMap<Bucket, Vec<RawItem>>
+    fn bulkdata2map<I, G, N>(
+        bulk: I,
+        upsert_value_gen: G,
+        namer: &N,
+    ) -> BTreeMap<Bucket, Vec<RawItem>>
     where
         I: Iterator<Item = RawData>,
         G: UpsertValueGenerator,
+        N: BucketNamer,
     {
         let i = bulk
-            .map(|d: RawData| Self::from_data(d, &upsert_value_gen))
+            .map(|d: RawData| Self::from_data(d, &upsert_value_gen, namer))
             .flat_map(|v| v.into_iter());
         i.fold(BTreeMap::new(), |mut m, req| {
             let b: Bucket = req.bucket;
@@ -315,6 +518,59 @@ impl UpsertRequest<Vec<u8>, Vec<u8>> {
             }
         })
     }
+
+    fn bulkdata2map_with_policy<I, G, N>(
+        bulk: I,
+        upsert_value_gen: G,
+        namer: &N,
+        policy: MasterUpsertPolicy,
+    ) -> BTreeMap<Bucket, Vec<RawItem>>
+    where
+        I: Iterator<Item = RawData>,
+        G: UpsertValueGenerator,
+        N: BucketNamer,
+    {
+        if MasterUpsertPolicy::Every.eq(&policy) {
+            return Self::bulkdata2map(bulk, upsert_value_gen, namer);
+        }
+        let mut m: BTreeMap<Bucket, Vec<RawItem>> = BTreeMap::new();
+        let mut masters: BTreeMap<(Bucket, Vec<u8>), RawItem> = BTreeMap::new();
+        let mut put = |b: Bucket, entity: &[u8], gen: &dyn Fn() -> RawItem| {
+            let k: (Bucket, Vec<u8>) = (b, entity.to_vec());
+            match (policy, masters.contains_key(&k)) {
+                (MasterUpsertPolicy::OncePerBatch, true) => {}
+                (MasterUpsertPolicy::FirstSeen, true) => {
+                    gen();
+                }
+                _ => {
+                    masters.insert(k, gen());
+                }
+            }
+        };
+        for d in bulk {
+            let dev: Device = d.as_device().clone();
+            let date: Date = d.as_date().clone();
+            put(namer.dates4device(&dev), date.as_bytes(), &|| {
+                upsert_value_gen.dates4device(&date)
+            });
+            put(namer.devices4date(&date), dev.as_bytes(), &|| {
+                upsert_value_gen.devices4date(&dev)
+            });
+            put(namer.dates(), date.as_bytes(), &|| {
+                upsert_value_gen.dates(&date)
+            });
+            put(namer.devices(), dev.as_bytes(), &|| {
+                upsert_value_gen.devices(&dev)
+            });
+            m.entry(namer.data(&dev, &date))
+                .or_default()
+                .push(d.into_item());
+        }
+        for ((b, _), i) in masters {
+            m.entry(b).or_default().push(i);
+        }
+        m
+    }
 }
 
 /// Converts single key/value pair(with device/date info) into (bucket/item) pairs.
@@ -326,17 +582,51 @@ pub fn rawdata2pairs<G>(r: RawData, upsert_value_gen: &G) -> impl Iterator<Item
 where
     G: UpsertValueGenerator,
 {
-    let reqs: Vec<UpsertRequest<Vec<u8>, Vec<u8>>> = UpsertRequest::from_data(r, upsert_value_gen);
+    let namer = SimpleBucketNamer::default();
+    let reqs: Vec<UpsertRequest<Vec<u8>, Vec<u8>>> =
+        UpsertRequest::from_data(r, upsert_value_gen, &namer);
     reqs.into_iter().map(|u: UpsertRequest<_, _>| u.into_pair())
 }
 
-fn rawdata2requests<I, G>(i: I, upsert_value_gen: G) -> impl Iterator<Item = (Bucket, Vec<RawItem>)>
+/// Items of a data bucket and master rows which refer to the data bucket.
+pub(crate) type DataGroup = (Vec<RawItem>, Vec<(Bucket, RawItem)>);
+
+/// Groups data by data bucket(with master rows).
+pub(crate) fn rawdata2groups<I, G, N>(
+    source: I,
+    upsert_value_gen: &G,
+    namer: &N,
+) -> BTreeMap<Bucket, DataGroup>
+where
+    I: Iterator<Item = RawData>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    source.fold(BTreeMap::new(), |mut m, d: RawData| {
+        let mut reqs = UpsertRequest::from_data(d, upsert_value_gen, namer).into_iter();
+        if let Some(data) = reqs.next() {
+            let (b, i) = data.into_pair();
+            let g: &mut DataGroup = m.entry(b).or_default();
+            g.0.push(i);
+            g.1.extend(reqs.map(|u: UpsertRequest<_, _>| u.into_pair()));
+        }
+        m
+    })
+}
+
+/// Groups data by bucket(data buckets and master buckets).
+#[cfg(feature = "async")]
+pub(crate) fn rawdata2map<I, G, N>(
+    source: I,
+    upsert_value_gen: G,
+    namer: &N,
+) -> BTreeMap<Bucket, Vec<RawItem>>
 where
     I: Iterator<Item = RawData>,
     G: UpsertValueGenerator,
+    N: BucketNamer,
 {
-    let m: BTreeMap<Bucket, Vec<RawItem>> = UpsertRequest::bulkdata2map(i, upsert_value_gen);
-    m.into_iter()
+    UpsertRequest::bulkdata2map(source, upsert_value_gen, namer)
 }
 
 fn upsert_into_bucket<U>(b: &Bucket, items: &[RawItem], upsert: &mut U) -> Result<u64, Event>
@@ -373,8 +663,9 @@ where
     let mapd = source.map(conv);
     let inspected = mapd.inspect(inspect);
     let noerr = inspected.flat_map(|r: Result<RawData, _>| r.ok());
-    let flat =
-        noerr.flat_map(|r: RawData| UpsertRequest::from_data(r, &upsert_value_gen).into_iter());
+    let namer = SimpleBucketNamer::default();
+    let flat = noerr
+        .flat_map(|r: RawData| UpsertRequest::from_data(r, &upsert_value_gen, &namer).into_iter());
     let mut pairs = flat.map(|u: UpsertRequest<_, _>| (u.bucket, u.item));
     pairs.try_fold(0, |tot, (bucket, item)| {
         upsert(&bucket, &item).map(|cnt| cnt + tot)
@@ -384,6 +675,7 @@ where
 /// Saves data got from source which uses a closure to actually save data.
 ///
 /// Duplicates will be ignored.
+/// Items are upserted one by one; use `upsert_all_batch_ex` for `BatchUpsert` backends.
 ///
 /// # Arguments
 /// - source: `RawData` source iterator.
@@ -395,14 +687,172 @@ where
     U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
     G: UpsertValueGenerator,
 {
-    let mut requests = rawdata2requests(source, upsert_value_gen);
-    requests.try_fold(0, |tot, req| {
+    let namer = SimpleBucketNamer::default();
+    upsert_all_with_namer(source, upsert, upsert_value_gen, &namer)
+}
+
+/// Saves data got from source using the namer to get bucket names.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert: Data saver which saves data into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Creates bucket names.
+pub fn upsert_all_with_namer<I, U, G, N>(
+    source: I,
+    upsert: &mut U,
+    upsert_value_gen: G,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    let mut upsert_batch = |b: &Bucket, items: &[RawItem]| upsert_into_bucket(b, items, upsert);
+    upsert_all_batch_with_namer(source, &mut upsert_batch, upsert_value_gen, namer)
+}
+
+/// Saves data got from source per bucket using the namer to get bucket names.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert_batch: Data saver which saves items into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Creates bucket names.
+pub fn upsert_all_batch_with_namer<I, B, G, N>(
+    source: I,
+    upsert_batch: &mut B,
+    upsert_value_gen: G,
+    namer: &N,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    B: FnMut(&Bucket, &[RawItem]) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    upsert_all_batch_with_policy(
+        source,
+        upsert_batch,
+        upsert_value_gen,
+        namer,
+        MasterUpsertPolicy::Every,
+    )
+}
+
+/// Saves data got from source per bucket using the policy to upsert master rows.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert_batch: Data saver which saves items into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Creates bucket names.
+/// - policy: Decides how master rows are upserted.
+pub fn upsert_all_batch_with_policy<I, B, G, N>(
+    source: I,
+    upsert_batch: &mut B,
+    upsert_value_gen: G,
+    namer: &N,
+    policy: MasterUpsertPolicy,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    B: FnMut(&Bucket, &[RawItem]) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    let m: BTreeMap<Bucket, Vec<RawItem>> =
+        UpsertRequest::bulkdata2map_with_policy(source, upsert_value_gen, namer, policy);
+    m.into_iter().try_fold(0, |tot, req| {
         let (bucket, v) = req;
         let uniq: Vec<RawItem> = Item::uniq(v);
-        upsert_into_bucket(&bucket, &uniq, upsert).map(|cnt| cnt + tot)
+        upsert_batch(&bucket, &uniq).map(|cnt| cnt + tot)
     })
 }
 
+/// Saves data got from source using the policy to upsert master rows.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert: Data saver which saves data into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - policy: Decides how master rows are upserted.
+pub fn upsert_all_with_policy<I, U, G>(
+    source: I,
+    upsert: &mut U,
+    upsert_value_gen: G,
+    policy: MasterUpsertPolicy,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    let namer = SimpleBucketNamer::default();
+    let mut upsert_batch = |b: &Bucket, items: &[RawItem]| upsert_into_bucket(b, items, upsert);
+    upsert_all_batch_with_policy(source, &mut upsert_batch, upsert_value_gen, &namer, policy)
+}
+
+/// Saves data got from source per bucket which uses a closure to actually save data.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert_batch: Data saver which saves items into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+pub fn upsert_all_batch_ex<I, B, G>(
+    source: I,
+    upsert_batch: &mut B,
+    upsert_value_gen: G,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    B: FnMut(&Bucket, &[RawItem]) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    upsert_all_batch_ex_with_policy(
+        source,
+        upsert_batch,
+        upsert_value_gen,
+        MasterUpsertPolicy::Every,
+    )
+}
+
+/// Saves data got from source per bucket using the policy to upsert master rows.
+///
+/// Duplicates will be ignored.
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert_batch: Data saver which saves items into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - policy: Decides how master rows are upserted.
+pub fn upsert_all_batch_ex_with_policy<I, B, G>(
+    source: I,
+    upsert_batch: &mut B,
+    upsert_value_gen: G,
+    policy: MasterUpsertPolicy,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    B: FnMut(&Bucket, &[RawItem]) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    let namer = SimpleBucketNamer::default();
+    upsert_all_batch_with_policy(source, upsert_batch, upsert_value_gen, &namer, policy)
+}
+
 /// Saves data got from source which uses a closure to actually save data.
 ///
 /// Duplicates will be ignored.
@@ -419,6 +869,85 @@ where
     upsert_all_ex(source, upsert, upsert_value_gen)
 }
 
+/// Saves data got from source window by window using the namer to get bucket names.
+///
+/// At most `window` `RawData` are buffered(grouped by bucket) before upserts.
+///
+/// - Buckets are processed in bucket name order within a window, windows are processed in source order.
+/// - Duplicates will be ignored within a window only(a duplicate in other windows will be upserted again).
+/// - Master rows are upserted for every window which refers to them.
+/// - The result is the sum of upsert results of all windows.
+/// - Items upserted before an error are not rolled back(windows after the error are not read).
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert: Data saver which saves data into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - namer: Creates bucket names.
+/// - window: Max number of `RawData` to be buffered.
+pub fn upsert_all_windowed_with_namer<I, U, G, N>(
+    source: I,
+    upsert: &mut U,
+    upsert_value_gen: G,
+    namer: &N,
+    window: NonZeroUsize,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+    N: BucketNamer,
+{
+    let mut source = source.peekable();
+    let mut tot: u64 = 0;
+    while source.peek().is_some() {
+        let chunk = source.by_ref().take(window.get());
+        tot += upsert_all_with_namer(chunk, upsert, &upsert_value_gen, namer)?;
+    }
+    Ok(tot)
+}
+
+/// Saves data got from source window by window(see `upsert_all_windowed_with_namer`).
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert: Data saver which saves data into specified bucket.
+/// - upsert_value_gen: Value generator for master buckets.
+/// - window: Max number of `RawData` to be buffered.
+pub fn upsert_all_windowed_ex<I, U, G>(
+    source: I,
+    upsert: &mut U,
+    upsert_value_gen: G,
+    window: NonZeroUsize,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
+    G: UpsertValueGenerator,
+{
+    let namer = SimpleBucketNamer::default();
+    upsert_all_windowed_with_namer(source, upsert, upsert_value_gen, &namer, window)
+}
+
+/// Saves data got from source window by window(see `upsert_all_windowed_with_namer`).
+///
+/// # Arguments
+/// - source: `RawData` source iterator.
+/// - upsert: Data saver which saves data into specified bucket.
+/// - window: Max number of `RawData` to be buffered.
+pub fn upsert_all_windowed<I, U>(
+    source: I,
+    upsert: &mut U,
+    window: NonZeroUsize,
+) -> Result<u64, Event>
+where
+    I: Iterator<Item = RawData>,
+    U: FnMut(&Bucket, &RawItem) -> Result<u64, Event>,
+{
+    let upsert_value_gen = upsert_value_generator_new_func_default();
+    upsert_all_windowed_ex(source, upsert, upsert_value_gen, window)
+}
+
 /// Creates a bucket before upsert.
 pub fn create_upsert<CU>(cu: &mut CU, b: &Bucket, i: &RawItem) -> Result<u64, Event>
 where
@@ -429,6 +958,16 @@ where
     Ok(cnt_c + cnt_u)
 }
 
+/// Creates a bucket before batch upsert.
+pub fn create_upsert_batch<CU>(cu: &mut CU, b: &Bucket, items: &[RawItem]) -> Result<u64, Event>
+where
+    CU: BatchUpsert + Create,
+{
+    let cnt_c: u64 = cu.create(b)?;
+    let cnt_u: u64 = cu.upsert_batch(b, items)?;
+    Ok(cnt_c + cnt_u)
+}
+
 struct CreateBeforeUpsert<C, U> {
     create: C,
     upsert: U,
@@ -450,8 +989,9 @@ where
     fn upsert(&mut self, b: &Bucket, i: &RawItem) -> Result<u64, Event> {
         (self.upsert)(b, i)
     }
+    /// Nothing to finalize(closures finalize nothing).
     fn finalize(self) -> Result<(), Event> {
-        Err(Event::UnexpectedError(String::from("Not implemented")))
+        Ok(())
     }
 }
 
@@ -619,4 +1159,244 @@ mod test_upsert {
             assert_eq!(pairs.len(), 5);
         }
     }
+
+    mod dedup_by_key {
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::upsert;
+
+        #[test]
+        fn test_last_wins() {
+            let items: Vec<RawItem> = vec![
+                Item::new(b"b".to_vec(), b"1".to_vec()),
+                Item::new(b"a".to_vec(), b"2".to_vec()),
+                Item::new(b"b".to_vec(), b"3".to_vec()),
+            ];
+            let uniq: Vec<&RawItem> = upsert::dedup_by_key(&items);
+            assert_eq!(uniq, vec![&items[1], &items[2]]);
+        }
+    }
+
+    mod upsert_all_batch_ex {
+        use crate::bucket::Bucket;
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{data::Data, date::Date, device::Device};
+
+        #[test]
+        fn test_fallback() {
+            let mut m: MemStore = MemStore::new();
+            let source = ["2022_11_01", "2022_11_02"].into_iter().map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            });
+            let mut calls: u64 = 0;
+            let mut upst = |b: &Bucket, items: &[RawItem]| {
+                calls += 1;
+                upsert::create_upsert_batch(&mut m, b, items)
+            };
+            let gen = upsert::upsert_value_generator_new_func_default();
+            let cnt: u64 = upsert::upsert_all_batch_ex(source, &mut upst, gen).unwrap();
+            assert_eq!(calls, 7);
+            // 7 buckets created, 9 rows upserted
+            assert_eq!(cnt, 7 + 9);
+            assert_eq!(m.count(&Bucket::new_dates_master()).unwrap(), 2);
+        }
+    }
+
+    mod upsert_all_with_policy {
+        use std::cell::Cell;
+
+        use crate::bucket::Bucket;
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::create::Create;
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert::{self, MasterUpsertPolicy, UpsertRaw};
+        use crate::{
+            data::{Data, RawData},
+            date::Date,
+            device::Device,
+        };
+
+        fn source() -> impl Iterator<Item = RawData> {
+            ["00:30:21.0Z", "00:30:22.0Z", "00:30:23.0Z"]
+                .into_iter()
+                .map(|k: &str| {
+                    Data::new(
+                        Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                        Date::new_unchecked("2022_11_02".into()),
+                        Item::new(k.into(), b"42".to_vec()),
+                    )
+                })
+        }
+
+        /// Upserts using a generator which generates different values(like timestamps).
+        fn run(policy: MasterUpsertPolicy) -> (MemStore, u32, Vec<Bucket>) {
+            let calls: Cell<u32> = Cell::new(0);
+            let next = || {
+                calls.set(calls.get() + 1);
+                calls.get().to_be_bytes().to_vec()
+            };
+            let gen = upsert::upsert_value_generator_new_func(
+                |d: &Date| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Device| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Date| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Device| Item::new(d.as_bytes().to_vec(), next()),
+            );
+            let mut m: MemStore = MemStore::new();
+            let mut upserted: Vec<Bucket> = vec![];
+            let mut cu = |b: &Bucket, i: &RawItem| {
+                upserted.push(b.clone());
+                upsert::create_upsert(&mut m, b, i)
+            };
+            upsert::upsert_all_with_policy(source(), &mut cu, gen, policy).unwrap();
+            (m, calls.get(), upserted)
+        }
+
+        fn dates(m: &MemStore) -> Vec<u8> {
+            let items: Vec<RawItem> = m.items(&Bucket::new_dates_master()).unwrap();
+            assert_eq!(items.len(), 1);
+            items[0].as_val().clone()
+        }
+
+        #[test]
+        fn test_every() {
+            let (m, calls, upserted) = run(MasterUpsertPolicy::default());
+            assert_eq!(calls, 12);
+            assert_eq!(upserted.len(), 3 * 5);
+            // the last upserted value(sorted by value) remains
+            assert_eq!(dates(&m), 11u32.to_be_bytes());
+        }
+
+        #[test]
+        fn test_once_per_batch() {
+            let (m, calls, upserted) = run(MasterUpsertPolicy::OncePerBatch);
+            assert_eq!(calls, 4);
+            assert_eq!(upserted.len(), 3 + 4);
+            assert_eq!(dates(&m), 3u32.to_be_bytes());
+        }
+
+        #[test]
+        fn test_first_seen() {
+            let (m, calls, upserted) = run(MasterUpsertPolicy::FirstSeen);
+            assert_eq!(calls, 12);
+            assert_eq!(upserted.len(), 3 + 4);
+            assert_eq!(dates(&m), 3u32.to_be_bytes());
+        }
+
+        #[test]
+        fn test_shared_batch() {
+            let calls: Cell<u32> = Cell::new(0);
+            let next = || {
+                calls.set(calls.get() + 1);
+                calls.get().to_be_bytes().to_vec()
+            };
+            let gen = upsert::upsert_value_generator_new_func(
+                |d: &Date| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Device| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Date| Item::new(d.as_bytes().to_vec(), next()),
+                |d: &Device| Item::new(d.as_bytes().to_vec(), next()),
+            );
+            let upsert_batch = |m: &mut &mut MemStore, b: &Bucket, items: &[RawItem]| {
+                items
+                    .iter()
+                    .try_fold(0, |tot, i| UpsertRaw::upsert(*m, b, i).map(|cnt| cnt + tot))
+            };
+            let mut m: MemStore = MemStore::new();
+            upsert::upsert_all_shared_batch_with_policy(
+                upsert_batch,
+                |m: &mut &mut MemStore, b: &Bucket| Create::create(*m, b),
+                &mut m,
+                |_| Ok(()),
+                source(),
+                gen,
+                MasterUpsertPolicy::LastSeen,
+            )
+            .unwrap();
+            assert_eq!(calls.get(), 12);
+            assert_eq!(dates(&m), 11u32.to_be_bytes());
+        }
+
+        #[test]
+        fn test_last_seen() {
+            let (m, calls, upserted) = run(MasterUpsertPolicy::LastSeen);
+            assert_eq!(calls, 12);
+            assert_eq!(upserted.len(), 3 + 4);
+            assert_eq!(dates(&m), 11u32.to_be_bytes());
+        }
+    }
+
+    mod upsert_all_windowed {
+        use std::num::NonZeroUsize;
+
+        use crate::bucket::Bucket;
+        use crate::item::{Item, RawItem};
+        use crate::kvstore::mem::MemStore;
+        use crate::kvstore::upsert;
+        use crate::{
+            data::{Data, RawData},
+            date::Date,
+            device::Device,
+        };
+
+        fn source() -> impl Iterator<Item = RawData> {
+            [
+                "2022_11_01",
+                "2022_11_02",
+                "2022_11_01",
+                "2022_11_03",
+                "2022_11_01",
+            ]
+            .into_iter()
+            .map(|d: &str| {
+                Data::new(
+                    Device::new_unchecked("cafef00ddeadbeafface864299792458".into()),
+                    Date::new_unchecked(d.into()),
+                    Item::new(b"00:30:21.0Z".to_vec(), b"42".to_vec()),
+                )
+            })
+        }
+
+        #[test]
+        fn test_same_as_buffered() {
+            let mut buffered: MemStore = MemStore::new();
+            let mut cu = |b: &Bucket, i: &_| upsert::create_upsert(&mut buffered, b, i);
+            upsert::upsert_all(source(), &mut cu).unwrap();
+
+            let mut windowed: MemStore = MemStore::new();
+            let mut calls: Vec<Bucket> = vec![];
+            let mut cu = |b: &Bucket, i: &RawItem| {
+                calls.push(b.clone());
+                upsert::create_upsert(&mut windowed, b, i)
+            };
+            let window = NonZeroUsize::new(2).unwrap();
+            upsert::upsert_all_windowed(source(), &mut cu, window).unwrap();
+
+            // 3 windows: [11_01, 11_02], [11_01, 11_03], [11_01]
+            let dates: usize = calls.iter().filter(|b| b.as_str().eq("dates")).count();
+            assert_eq!(dates, 5);
+            let data1101: usize = calls
+                .iter()
+                .filter(|b| b.as_str().starts_with("data_2022_11_01"))
+                .count();
+            assert_eq!(data1101, 3);
+            assert_eq!(windowed, buffered);
+        }
+
+        #[test]
+        fn test_uniq_in_window() {
+            let mut upst = |_: &Bucket, _: &RawItem| Ok(1);
+            let all = NonZeroUsize::new(5).unwrap();
+            let upserted: u64 = upsert::upsert_all_windowed(source(), &mut upst, all).unwrap();
+            // data(3) + dates_{device}(3) + devices_{date}(3) + dates(3) + devices(1)
+            assert_eq!(upserted, 13);
+
+            let one = NonZeroUsize::new(1).unwrap();
+            let upserted: u64 = upsert::upsert_all_windowed(source(), &mut upst, one).unwrap();
+            assert_eq!(upserted, 5 * 5);
+        }
+    }
 }
@@ -3,6 +3,7 @@ pub mod compose;
 pub mod count;
 pub mod data;
 pub mod date;
+pub mod daterange;
 pub mod datetime;
 pub mod day;
 pub mod device;
@@ -10,5 +11,7 @@ pub mod evt;
 pub mod item;
 pub mod kvstore;
 pub mod month;
+pub mod namer;
 pub mod remove;
+pub mod weekday;
 pub mod year;
@@ -1,8 +1,8 @@
 //! Simple Month(Jan, Feb, Mar, ... , Dec)
 
-use crate::evt::Event;
+use crate::{evt::Event, year::Year};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Month {
     Jan,
     Feb,
@@ -22,6 +22,18 @@ impl Month {
     pub fn as_raw(&self) -> u8 {
         u8::from(*self)
     }
+
+    /// Gets the number of days of this month in the year.
+    pub fn days(&self, y: &Year) -> u8 {
+        match self {
+            Self::Feb => match y.is_leap() {
+                true => 29,
+                false => 28,
+            },
+            Self::Apr | Self::Jun | Self::Sep | Self::Nov => 30,
+            _ => 31,
+        }
+    }
 }
 
 impl From<Month> for u8 {
@@ -123,4 +135,19 @@ mod test_month {
             assert_eq!(r.is_err(), true);
         }
     }
+    mod days {
+        use crate::month::Month;
+        use crate::year::Year;
+
+        #[test]
+        fn test_days() {
+            let leap: Year = Year::try_from(2024).unwrap();
+            let common: Year = Year::try_from(2023).unwrap();
+            assert_eq!(Month::Jan.days(&common), 31);
+            assert_eq!(Month::Feb.days(&common), 28);
+            assert_eq!(Month::Feb.days(&leap), 29);
+            assert_eq!(Month::Apr.days(&leap), 30);
+            assert_eq!(Month::Dec.days(&leap), 31);
+        }
+    }
 }
@@ -0,0 +1,284 @@
+//! Bucket naming scheme.
+
+use crate::{
+    bucket::{Bucket, BucketKind},
+    date::Date,
+    device::Device,
+    evt::Event,
+};
+
+/// Creates/parses bucket names.
+///
+/// `parse` must accept every name created by this namer.
+pub trait BucketNamer {
+    /// Creates new `Bucket` for data.
+    fn data(&self, dev: &Device, date: &Date) -> Bucket;
+
+    /// Creates new `Bucket` for dates master for device.
+    fn dates4device(&self, dev: &Device) -> Bucket;
+
+    /// Creates new `Bucket` for devices master for date.
+    fn devices4date(&self, date: &Date) -> Bucket;
+
+    /// Creates new `Bucket` for dates master.
+    fn dates(&self) -> Bucket;
+
+    /// Creates new `Bucket` for devices master.
+    fn devices(&self) -> Bucket;
+
+    /// Parses the bucket name.
+    fn parse(&self, b: &Bucket) -> Result<BucketKind, Event>;
+}
+
+/// Configurable namer: `{prefix}data{separator}{date}{separator}{device}`.
+///
+/// The default namer creates names like below.
+/// - data_2022_11_07_cafef00ddeadbeafface864299792458
+/// - dates_cafef00ddeadbeafface864299792458
+/// - devices_2022_11_07
+/// - dates
+/// - devices
+#[derive(Debug, Clone)]
+pub struct SimpleBucketNamer {
+    prefix: String,
+    separator: String,
+    device_first: bool,
+}
+
+impl Default for SimpleBucketNamer {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            separator: String::from("_"),
+            device_first: false,
+        }
+    }
+}
+
+fn is_identifier_body(b: &[u8]) -> bool {
+    b.iter()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b'_'.eq(c))
+}
+
+impl SimpleBucketNamer {
+    /// Sets the prefix(example: "telemetry_").
+    ///
+    /// The prefix must start with `[a-z_]` and contain `[a-z0-9_]` only
+    /// (names must be safe identifiers for backends like sqlite/postgres).
+    pub fn with_prefix(mut self, prefix: String) -> Result<Self, Event> {
+        let b: &[u8] = prefix.as_bytes();
+        let valid_head: bool = b
+            .first()
+            .map(|c| c.is_ascii_lowercase() || b'_'.eq(c))
+            .unwrap_or(true);
+        match valid_head && is_identifier_body(b) {
+            true => {
+                self.prefix = prefix;
+                Ok(self)
+            }
+            false => Err(Event::UnsafeIdentifier(format!(
+                "Invalid prefix: {}",
+                prefix
+            ))),
+        }
+    }
+
+    /// Sets the separator(must not be empty, `[a-z0-9_]` only).
+    pub fn with_separator(mut self, separator: String) -> Result<Self, Event> {
+        match (
+            separator.is_empty(),
+            is_identifier_body(separator.as_bytes()),
+        ) {
+            (true, _) => Err(Event::InvalidBucket(String::from("Empty separator"))),
+            (false, false) => Err(Event::UnsafeIdentifier(format!(
+                "Invalid separator: {}",
+                separator
+            ))),
+            (false, true) => {
+                self.separator = separator;
+                Ok(self)
+            }
+        }
+    }
+
+    /// Puts the device before the date in data bucket names.
+    pub fn with_device_first(mut self, device_first: bool) -> Self {
+        self.device_first = device_first;
+        self
+    }
+
+    fn name(&self, parts: &[&str]) -> Bucket {
+        let name: String = format!("{}{}", self.prefix, parts.join(&self.separator));
+        Bucket::from(name)
+    }
+
+    fn parse_data(&self, s: &str) -> Result<BucketKind, Event> {
+        let invalid = || Event::InvalidBucket(format!("Invalid data bucket: {}", s));
+        let sep: &str = self.separator.as_str();
+        // YYYY_MM_DD
+        let date_len: usize = 10;
+        let (ds, device): (&str, &str) = match self.device_first {
+            false => {
+                let ds: &str = s.get(..date_len).ok_or_else(invalid)?;
+                let rest: &str = s.get(date_len..).ok_or_else(invalid)?;
+                (ds, rest.strip_prefix(sep).ok_or_else(invalid)?)
+            }
+            true => {
+                let split: usize = s.len().checked_sub(date_len).ok_or_else(invalid)?;
+                let ds: &str = s.get(split..).ok_or_else(invalid)?;
+                let rest: &str = s.get(..split).ok_or_else(invalid)?;
+                (ds, rest.strip_suffix(sep).ok_or_else(invalid)?)
+            }
+        };
+        (!device.is_empty()).then_some(()).ok_or_else(invalid)?;
+        let date: Date = Date::parse(ds)?;
+        let device: Device = Device::new_unchecked(device.into());
+        Ok(BucketKind::Data { date, device })
+    }
+
+    fn parse_unprefixed(&self, s: &str) -> Result<BucketKind, Event> {
+        let sep: &str = self.separator.as_str();
+        let strip = |head: &str| s.strip_prefix(head).and_then(|t| t.strip_prefix(sep));
+        match (s, strip("data"), strip("dates"), strip("devices")) {
+            ("dates", _, _, _) => Ok(BucketKind::Dates),
+            ("devices", _, _, _) => Ok(BucketKind::Devices),
+            (_, Some(rest), _, _) => self.parse_data(rest),
+            (_, _, Some(""), _) => Err(Event::InvalidBucket(format!("No device: {}", s))),
+            (_, _, Some(dev), _) => Ok(BucketKind::DatesForDevice(Device::new_unchecked(
+                dev.into(),
+            ))),
+            (_, _, _, Some(ds)) => Date::parse(ds).map(BucketKind::DevicesForDate),
+            _ => Ok(BucketKind::Unknown),
+        }
+    }
+}
+
+impl BucketNamer for SimpleBucketNamer {
+    fn data(&self, dev: &Device, date: &Date) -> Bucket {
+        match self.device_first {
+            false => self.name(&["data", date.as_str(), dev.as_str()]),
+            true => self.name(&["data", dev.as_str(), date.as_str()]),
+        }
+    }
+
+    fn dates4device(&self, dev: &Device) -> Bucket {
+        self.name(&["dates", dev.as_str()])
+    }
+
+    fn devices4date(&self, date: &Date) -> Bucket {
+        self.name(&["devices", date.as_str()])
+    }
+
+    fn dates(&self) -> Bucket {
+        self.name(&["dates"])
+    }
+
+    fn devices(&self) -> Bucket {
+        self.name(&["devices"])
+    }
+
+    fn parse(&self, b: &Bucket) -> Result<BucketKind, Event> {
+        let bs: &str = b.as_str();
+        bs.strip_prefix(self.prefix.as_str())
+            .map(|s: &str| self.parse_unprefixed(s))
+            .unwrap_or(Ok(BucketKind::Unknown))
+    }
+}
+
+#[cfg(test)]
+mod test_namer {
+
+    mod simple {
+
+        use crate::bucket::{Bucket, BucketKind};
+        use crate::namer::{BucketNamer, SimpleBucketNamer};
+        use crate::{date::Date, device::Device};
+
+        fn kinds() -> Vec<BucketKind> {
+            let dev = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date = Date::new_unchecked("2022_11_02".into());
+            vec![
+                BucketKind::Data {
+                    date: date.clone(),
+                    device: dev.clone(),
+                },
+                BucketKind::DatesForDevice(dev),
+                BucketKind::DevicesForDate(date),
+                BucketKind::Dates,
+                BucketKind::Devices,
+            ]
+        }
+
+        fn bucket<N>(n: &N, k: &BucketKind) -> Bucket
+        where
+            N: BucketNamer,
+        {
+            match k {
+                BucketKind::Data { date, device } => n.data(device, date),
+                BucketKind::DatesForDevice(device) => n.dates4device(device),
+                BucketKind::DevicesForDate(date) => n.devices4date(date),
+                BucketKind::Dates => n.dates(),
+                BucketKind::Devices => n.devices(),
+                BucketKind::Unknown => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn test_default() {
+            let n = SimpleBucketNamer::default();
+            for k in kinds() {
+                let b: Bucket = bucket(&n, &k);
+                assert_eq!(Some(b.clone()), k.to_bucket());
+                assert_eq!(n.parse(&b).unwrap(), k);
+            }
+        }
+
+        #[test]
+        fn test_custom() {
+            let n = SimpleBucketNamer::default()
+                .with_prefix("telemetry_".into())
+                .unwrap()
+                .with_separator("__".into())
+                .unwrap()
+                .with_device_first(true);
+            let names: Vec<String> = kinds()
+                .iter()
+                .map(|k| String::from(bucket(&n, k)))
+                .collect();
+            assert_eq!(
+                names,
+                vec![
+                    "telemetry_data__cafef00ddeadbeafface864299792458__2022_11_02",
+                    "telemetry_dates__cafef00ddeadbeafface864299792458",
+                    "telemetry_devices__2022_11_02",
+                    "telemetry_dates",
+                    "telemetry_devices",
+                ]
+            );
+            for k in kinds() {
+                let b: Bucket = bucket(&n, &k);
+                assert_eq!(n.parse(&b).unwrap(), k);
+            }
+
+            let other: Bucket = Bucket::from(String::from("devices_2022_11_02"));
+            assert_eq!(n.parse(&other).unwrap(), BucketKind::Unknown);
+        }
+
+        #[test]
+        fn test_empty_separator() {
+            let r: Result<_, _> = SimpleBucketNamer::default().with_separator("".into());
+            assert!(r.is_err());
+        }
+
+        #[test]
+        fn test_unsafe() {
+            let n = || SimpleBucketNamer::default();
+            assert!(n().with_prefix("telemetry.".into()).is_err());
+            assert!(n().with_prefix("0telemetry_".into()).is_err());
+            assert!(n().with_prefix("Telemetry_".into()).is_err());
+            assert!(n().with_prefix(String::new()).is_ok());
+            assert!(n().with_separator("-".into()).is_err());
+            assert!(n().with_separator("_x_".into()).is_ok());
+        }
+    }
+}
@@ -1,18 +1,27 @@
 //! Removes buckets or keys by date or device.
 
 use crate::{
-    bucket::Bucket, date::Date, day::Day, device::Device, evt::Event, month::Month, year::Year,
+    bucket::{Bucket, BucketKind},
+    date::Date,
+    device::Device,
+    namer::{BucketNamer, SimpleBucketNamer},
 };
 
-fn is_drop_target_str(bs: &str, ds: &str) -> Result<bool, Event> {
-    let pat = format!("data_{}", ds);
-    Ok(bs.starts_with(&pat))
-}
-
-fn is_drop_target_device_str(bs: &str, ds: &str) -> Result<bool, Event> {
-    let head = "data_";
-    let tail = format!("_{}", ds);
-    Ok(bs.starts_with(head) && bs.ends_with(&tail))
+/// Checks if the bucket must be dropped using the namer.
+///
+/// # Arguments
+/// - namer: Parses the bucket name.
+/// - b: The bucket to be checked.
+/// - d: The device to be compared.
+pub fn is_drop_target_device_with_namer<N>(namer: &N, b: &Bucket, d: &Device) -> bool
+where
+    N: BucketNamer,
+{
+    match namer.parse(b) {
+        Ok(BucketKind::DatesForDevice(dev)) => dev.eq(d),
+        Ok(BucketKind::Data { device, .. }) => device.eq(d),
+        _ => false,
+    }
 }
 
 /// Checks if the bucket must be dropped.
@@ -23,13 +32,24 @@ fn is_drop_target_device_str(bs: &str, ds: &str) -> Result<bool, Event> {
 /// - b: The bucket to be checked.
 /// - d: The device to be compared.
 pub fn is_drop_target_device(b: &Bucket, d: &Device) -> bool {
-    let bs: &str = b.as_str();
-    let ds: &str = d.as_str();
+    is_drop_target_device_with_namer(&SimpleBucketNamer::default(), b, d)
+}
 
-    format!("dates_{}", ds)
-        .eq(bs)
-        .then_some(true)
-        .unwrap_or_else(|| is_drop_target_device_str(bs, ds).unwrap_or(false))
+/// Checks if the bucket must be dropped using the namer.
+///
+/// # Arguments
+/// - namer: Parses the bucket name.
+/// - b: The bucket to be checked.
+/// - d: The date to be compared.
+pub fn is_drop_target_with_namer<N>(namer: &N, b: &Bucket, d: &Date) -> bool
+where
+    N: BucketNamer,
+{
+    match namer.parse(b) {
+        Ok(BucketKind::DevicesForDate(date)) => date.eq(d),
+        Ok(BucketKind::Data { date, .. }) => date.eq(d),
+        _ => false,
+    }
 }
 
 /// Checks if the bucket must be dropped.
@@ -40,93 +60,56 @@ pub fn is_drop_target_device(b: &Bucket, d: &Device) -> bool {
 /// - b: The bucket to be checked.
 /// - d: The date to be compared.
 pub fn is_drop_target(b: &Bucket, d: &Date) -> bool {
-    let bs: &str = b.as_str();
-    let ds: &str = d.as_str();
-
-    format!("devices_{}", ds)
-        .eq(bs)
-        .then_some(true)
-        .unwrap_or_else(|| is_drop_target_str(bs, ds).unwrap_or(false))
-}
-
-fn is_delete_target_str(bs: &str) -> Result<bool, Event> {
-    Ok(bs.starts_with("dates_"))
+    is_drop_target_with_namer(&SimpleBucketNamer::default(), b, d)
 }
 
-fn is_delete_target_device_str(bs: &str) -> Result<bool, Event> {
-    Ok(bs.starts_with("devices_"))
+/// Checks if the bucket can have rows to be deleted using the namer.
+pub fn is_delete_target_device_with_namer<N>(namer: &N, b: &Bucket) -> bool
+where
+    N: BucketNamer,
+{
+    matches!(
+        namer.parse(b),
+        Ok(BucketKind::Devices) | Ok(BucketKind::DevicesForDate(_))
+    )
 }
 
 /// Checks if the bucket can have rows to be deleted.
 pub fn is_delete_target_device(b: &Bucket) -> bool {
-    let bs: &str = b.as_str();
+    is_delete_target_device_with_namer(&SimpleBucketNamer::default(), b)
+}
 
-    "devices"
-        .eq(bs)
-        .then_some(true)
-        .unwrap_or_else(|| is_delete_target_device_str(bs).unwrap_or(false))
+/// Checks if the bucket can have rows to be deleted using the namer.
+pub fn is_delete_target_with_namer<N>(namer: &N, b: &Bucket) -> bool
+where
+    N: BucketNamer,
+{
+    matches!(
+        namer.parse(b),
+        Ok(BucketKind::Dates) | Ok(BucketKind::DatesForDevice(_))
+    )
 }
 
 /// Checks if the bucket can have rows to be deleted.
 pub fn is_delete_target(b: &Bucket) -> bool {
-    let bs: &str = b.as_str();
-
-    "dates"
-        .eq(bs)
-        .then_some(true)
-        .unwrap_or_else(|| is_delete_target_str(bs).unwrap_or(false))
-}
-
-fn is_target_stale_compare_date(
-    year: &str,
-    month: &str,
-    day: &str,
-    lbi: &Date,
-) -> Result<bool, Event> {
-    let yu: u16 =
-        str::parse(year).map_err(|e| Event::UnexpectedError(format!("Invalid year: {}", e)))?;
-    let mu: u8 =
-        str::parse(month).map_err(|e| Event::UnexpectedError(format!("Invalid month: {}", e)))?;
-    let du: u8 =
-        str::parse(day).map_err(|e| Event::UnexpectedError(format!("Invalid day: {}", e)))?;
-    let y: Year = Year::try_from(yu)?;
-    let m: Month = Month::try_from(mu)?;
-    let d: Day = Day::try_from(du)?;
-    let dt: Date = Date::new(y, m, d);
-    let fresh: bool = lbi.le(&dt);
-    Ok(!fresh)
-}
-
-// data_2022_11_07_cafef00ddeadbeafface864299792458
-fn is_drop_target_stale_data_bucket(bs: &str, lbi: &Date) -> Result<bool, Event> {
-    let mut splited = bs.splitn(5, '_');
-    splited.next(); // data
-    let ys: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Year unknown")))?;
-    let ms: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Month unknown")))?;
-    let ds: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Day unknown")))?;
-    is_target_stale_compare_date(ys, ms, ds, lbi)
+    is_delete_target_with_namer(&SimpleBucketNamer::default(), b)
 }
 
-// devices_2022_11_07
-fn is_drop_target_stale_devices_master(bs: &str, lbi: &Date) -> Result<bool, Event> {
-    let mut splited = bs.splitn(4, '_');
-    splited.next(); // devices
-    let ys: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Year unknown")))?;
-    let ms: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Month unknown")))?;
-    let ds: &str = splited
-        .next()
-        .ok_or_else(|| Event::UnexpectedError(String::from("Day unknown")))?;
-    is_target_stale_compare_date(ys, ms, ds, lbi)
+/// Checks if the bucket must be dropped using the namer.
+///
+/// # Arguments
+/// - namer: Parses the bucket name.
+/// - b: The bucket to be checked.
+/// - lbi: Lower bound(inclusive) which must "not" be dropped.
+pub fn is_drop_target_stale_with_namer<N>(namer: &N, b: &Bucket, lbi: &Date) -> bool
+where
+    N: BucketNamer,
+{
+    match namer.parse(b) {
+        Ok(BucketKind::Data { date, .. }) => date.lt(lbi),
+        Ok(BucketKind::DevicesForDate(date)) => date.lt(lbi),
+        _ => false,
+    }
 }
 
 /// Checks if the bucket must be dropped.
@@ -135,12 +118,7 @@ fn is_drop_target_stale_devices_master(bs: &str, lbi: &Date) -> Result<bool, Eve
 /// - b: The bucket to be checked.
 /// - lbe: Lower bound(inclusive) which must "not" be dropped.
 pub fn is_drop_target_stale(b: &Bucket, lbi: &Date) -> bool {
-    let bs: &str = b.as_str();
-    let is_data_bucket: bool = bs.starts_with("data_");
-    is_data_bucket
-        .then(|| is_drop_target_stale_data_bucket(bs, lbi))
-        .unwrap_or_else(|| is_drop_target_stale_devices_master(bs, lbi))
-        .unwrap_or(false)
+    is_drop_target_stale_with_namer(&SimpleBucketNamer::default(), b, lbi)
 }
 
 #[cfg(test)]
@@ -216,6 +194,15 @@ mod test_remove {
             let d: Device = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
             assert_eq!(remove::is_drop_target_device(&b, &d), true);
         }
+
+        #[test]
+        fn test_invalid_data_bucket() {
+            let b: Bucket = Bucket::from(String::from(
+                "data_2022_13_19_cafef00ddeadbeafface864299792458",
+            ));
+            let d: Device = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            assert!(!remove::is_drop_target_device(&b, &d));
+        }
     }
 
     mod is_drop_target {
@@ -254,6 +241,41 @@ mod test_remove {
             let b: Bucket = Bucket::from(String::from("devices_2022_11_23"));
             assert_eq!(remove::is_delete_target_device(&b), true);
         }
+
+        #[test]
+        fn test_invalid_devices4date_master() {
+            let b: Bucket = Bucket::from(String::from("devices_2022_11_32"));
+            assert!(!remove::is_delete_target_device(&b));
+        }
+    }
+
+    mod with_namer {
+        use crate::remove;
+        use crate::{
+            bucket::Bucket, date::Date, device::Device, namer::BucketNamer,
+            namer::SimpleBucketNamer,
+        };
+
+        #[test]
+        fn test_device_first() {
+            let n = SimpleBucketNamer::default().with_device_first(true);
+            let dev: Device = Device::new_unchecked("cafef00ddeadbeafface864299792458".into());
+            let date: Date = Date::new_unchecked("2022_11_21".into());
+            let lbi: Date = Date::new_unchecked("2022_11_22".into());
+            let b: Bucket = n.data(&dev, &date);
+            assert!(remove::is_drop_target_stale_with_namer(&n, &b, &lbi));
+            assert!(remove::is_drop_target_with_namer(&n, &b, &date));
+            assert!(remove::is_drop_target_device_with_namer(&n, &b, &dev));
+
+            // default namer can not parse the date
+            assert!(!remove::is_drop_target_stale(&b, &lbi));
+
+            assert!(remove::is_delete_target_with_namer(&n, &n.dates()));
+            assert!(remove::is_delete_target_device_with_namer(
+                &n,
+                &n.devices4date(&date)
+            ));
+        }
     }
 
     mod is_delete_target {
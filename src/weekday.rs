@@ -0,0 +1,70 @@
+//! Simple Weekday(Mon, Tue, Wed, ... , Sun)
+
+use crate::evt::Event;
+
+/// Day of week(ISO 8601 numbering: Mon = 1, ... , Sun = 7).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub fn as_raw(&self) -> u8 {
+        u8::from(*self)
+    }
+}
+
+impl From<Weekday> for u8 {
+    fn from(w: Weekday) -> u8 {
+        match w {
+            Weekday::Mon => 1,
+            Weekday::Tue => 2,
+            Weekday::Wed => 3,
+            Weekday::Thu => 4,
+            Weekday::Fri => 5,
+            Weekday::Sat => 6,
+            Weekday::Sun => 7,
+        }
+    }
+}
+
+impl TryFrom<u8> for Weekday {
+    type Error = Event;
+
+    fn try_from(u: u8) -> Result<Self, Self::Error> {
+        match u {
+            1 => Ok(Self::Mon),
+            2 => Ok(Self::Tue),
+            3 => Ok(Self::Wed),
+            4 => Ok(Self::Thu),
+            5 => Ok(Self::Fri),
+            6 => Ok(Self::Sat),
+            7 => Ok(Self::Sun),
+            _ => Err(Event::InvalidDate(format!("Invalid weekday number: {}", u))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_weekday {
+
+    mod weekday {
+        use crate::weekday::Weekday;
+
+        #[test]
+        fn test_conv() {
+            for u in 1..=7 {
+                let w: Weekday = Weekday::try_from(u).unwrap();
+                assert_eq!(w.as_raw(), u);
+            }
+            assert!(Weekday::try_from(0).is_err());
+            assert!(Weekday::try_from(8).is_err());
+        }
+    }
+}
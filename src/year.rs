@@ -2,6 +2,7 @@
 
 use crate::evt::Event;
 
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct Year {
     y: u16,
 }
@@ -10,6 +11,12 @@ impl Year {
     pub fn as_raw(&self) -> u16 {
         self.y
     }
+
+    /// Checks if the year is a leap year(proleptic Gregorian calendar).
+    pub fn is_leap(&self) -> bool {
+        let y: u16 = self.y;
+        (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+    }
 }
 
 impl TryFrom<u16> for Year {
@@ -36,4 +43,17 @@ mod test_year {
             assert_eq!(r.is_err(), true);
         }
     }
+
+    mod is_leap {
+        use crate::year::Year;
+
+        #[test]
+        fn test_leap() {
+            let leap = |y: u16| Year::try_from(y).unwrap().is_leap();
+            assert!(leap(2024));
+            assert!(leap(2000));
+            assert!(!leap(2023));
+            assert!(!leap(1900));
+        }
+    }
 }